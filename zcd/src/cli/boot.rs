@@ -0,0 +1,51 @@
+//! System boot time, for `list --since-boot`.
+
+use std::fs;
+use std::process::Command;
+
+use crate::db::dir::Epoch;
+
+/// When the machine booted, or `None` where that cannot be determined.
+/// Linux reads `btime` from `/proc/stat`; macOS asks `sysctl`.
+pub fn boot_time() -> Option<Epoch> {
+    if cfg!(target_os = "linux") {
+        parse_proc_stat(&fs::read_to_string("/proc/stat").ok()?)
+    } else if cfg!(target_os = "macos") {
+        let output = Command::new("sysctl")
+            .args(["-n", "kern.boottime"])
+            .output()
+            .ok()?;
+        parse_sysctl_boottime(&String::from_utf8_lossy(&output.stdout))
+    } else {
+        None
+    }
+}
+
+/// `btime <epoch>` line of `/proc/stat`.
+fn parse_proc_stat(stat: &str) -> Option<Epoch> {
+    stat.lines()
+        .find_map(|line| line.strip_prefix("btime "))
+        .and_then(|value| value.trim().parse().ok())
+}
+
+/// `{ sec = 1700000000, usec = 123 } Tue Nov 14 ...` from macOS sysctl.
+fn parse_sysctl_boottime(output: &str) -> Option<Epoch> {
+    let rest = &output[output.find("sec =")? + "sec =".len()..];
+    rest.split(',').next()?.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod test_boot {
+    use super::*;
+
+    #[test]
+    fn parses_platform_boot_time_formats() {
+        let stat = "cpu  1 2 3\nintr 5\nbtime 1700000000\nprocesses 42\n";
+        assert_eq!(parse_proc_stat(stat), Some(1_700_000_000));
+        assert_eq!(parse_proc_stat("cpu 1 2 3\n"), None);
+
+        let sysctl = "{ sec = 1700000123, usec = 456 } Tue Nov 14 22:15:23 2023\n";
+        assert_eq!(parse_sysctl_boottime(sysctl), Some(1_700_000_123));
+        assert_eq!(parse_sysctl_boottime("garbage"), None);
+    }
+}
@@ -1,8 +1,15 @@
-use crate::config::config_file;
-use crate::db::dir::{Dir, OpsDelegate};
-use crate::db::Database;
+use crate::config::{config_files, expand_path, load_config_from_paths, Config};
+use crate::db::dir::{Dir, OpsDelegate, Ranking};
+use crate::db::{
+    usage, DataFormat, Database, DbStats, Delimiter, DirGroup, DirListDiff, QueryOptions,
+    QueryPage, ScoredDir, TsvColumns,
+};
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
+use std::borrow::Cow;
+use std::ffi::OsStr;
+use std::fs::{self, File};
+use std::io::{self, BufReader};
 use std::path::Path;
 
 pub struct Client {
@@ -11,43 +18,242 @@ pub struct Client {
 
 impl Client {
     pub fn new() -> Result<Self> {
-        let config_path = config_file().context("failed to find config file")?;
-        let database = Database::new(&config_path).context("failed to init database")?;
+        let config = load_config_from_paths(&config_files()).context("failed to load config")?;
+        Self::with_config(config)
+    }
+
+    pub fn with_config(config: Config) -> Result<Self> {
+        let database = Database::with_config(config).context("failed to init database")?;
         Ok(Client { db: database })
     }
 
-    pub fn insert(&mut self, s: &str) -> Result<()> {
-        self.db.insert_or_update(s.into());
+    pub fn insert(&mut self, s: &OsStr) -> Result<()> {
+        self.db.insert_os(s);
         self.db.save()
     }
 
+    /// Record a visit to `path` and return its canonical form for the
+    /// shell to `cd` into, so a jump needs a single process.
+    pub fn go(&mut self, path: &Path) -> Result<String> {
+        let expanded = expand_path(path)?;
+        let canonical = fs::canonicalize(&expanded)
+            .with_context(|| format!("cannot resolve {}", expanded.display()))?;
+        if !canonical.is_dir() {
+            bail!("not a directory: {}", canonical.display());
+        }
+        self.insert(canonical.as_os_str())?;
+        Ok(canonical.to_string_lossy().into_owned())
+    }
+
+    /// Insert every path listed in `path` (`-` reads stdin), saving once.
+    pub fn insert_from_file(&mut self, path: &Path) -> Result<usize> {
+        let count = if path == Path::new("-") {
+            self.db.insert_from_reader(io::stdin().lock())?
+        } else {
+            let file =
+                File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+            self.db.insert_from_reader(BufReader::new(file))?
+        };
+        self.db.save()?;
+        Ok(count)
+    }
+
     pub fn delete(&mut self, s: &str) -> Result<()> {
         self.db.delete(s);
         self.db.save()
     }
 
-    pub fn query(&self, pattern: &str) -> Option<Dir<'_>> {
-        self.db.query(pattern).into_iter().next()
+    /// Config-derived query options, to be adjusted per command.
+    pub fn query_options(&self) -> QueryOptions {
+        self.db.query_options()
     }
 
-    pub fn list(&self) -> Vec<Dir<'_>> {
-        self.db.list()
+    /// The one match `zcd query` jumps to, chosen like
+    /// [`Client::resolve`] but under the caller's `opts`.
+    pub fn query(&self, terms: &[&str], opts: &QueryOptions) -> Result<Option<ScoredDir<'_>>> {
+        Ok(self
+            .db
+            .resolve_terms(terms, opts)?
+            .map(|scored| self.mapped(scored)))
     }
 
-    pub fn import(&mut self, path: &Path) -> Result<usize> {
-        let count = self.db.import(path)?;
+    /// Record a visit to the match [`Client::query`] would jump to, whatever
+    /// page of matches was printed. Returns whether there was one.
+    pub fn bump(&mut self, terms: &[&str], opts: &QueryOptions) -> Result<bool> {
+        let Some(best) = self.db.resolve_terms(terms, opts)? else {
+            return Ok(false);
+        };
+        let best = best.dir.path.to_string();
+        self.insert(OsStr::new(&best))?;
+        Ok(true)
+    }
+
+    pub fn resolve(&self, pattern: &str) -> Result<Option<String>> {
+        Ok(self
+            .db
+            .resolve(pattern)?
+            .map(|path| self.db.map_path(&path).into_owned()))
+    }
+
+    /// Move the history of a renamed directory matching `terms` to its new
+    /// path (see [`Database::follow_rename`]) and return that path.
+    pub fn follow_rename(&mut self, terms: &[&str], opts: &QueryOptions) -> Result<Option<String>> {
+        let Some(path) = self.db.follow_rename(terms, opts) else {
+            return Ok(None);
+        };
+        self.db.save()?;
+        Ok(Some(self.db.map_path(&path).into_owned()))
+    }
+
+    pub fn count(&self, terms: &[&str], opts: &QueryOptions) -> usize {
+        self.db.count(terms, opts)
+    }
+
+    pub fn query_page(
+        &self,
+        terms: &[&str],
+        offset: usize,
+        limit: usize,
+        opts: &QueryOptions,
+    ) -> QueryPage<'_> {
+        let mut page = self.db.query_page(terms, offset, Some(limit), opts);
+        page.dirs = page.dirs.into_iter().map(|d| self.mapped(d)).collect();
+        page
+    }
+
+    /// `scored` with its path shown through the configured `path_map`.
+    /// Match positions no longer line up with a rewritten path, so they
+    /// are dropped.
+    fn mapped<'a>(&self, mut scored: ScoredDir<'a>) -> ScoredDir<'a> {
+        if let Cow::Owned(path) = self.db.map_path(&scored.dir.path) {
+            scored.dir.path = path.into();
+            scored.positions.clear();
+        }
+        scored
+    }
+
+    /// Stored rank of `path`, i.e. its accumulated visits.
+    pub fn visits(&self, path: &str) -> Ranking {
+        self.db.get(path).map_or(0.0, |dir| dir.rank)
+    }
+
+    pub fn list(&self, opts: &QueryOptions) -> Vec<Dir<'_>> {
+        self.list_with_visits(opts)
+            .into_iter()
+            .map(|(dir, _)| dir)
+            .collect()
+    }
+
+    /// [`Client::list`] with each entry's visits, looked up under its stored
+    /// path before `path_map` rewrites it.
+    pub fn list_with_visits(&self, opts: &QueryOptions) -> Vec<(Dir<'_>, Ranking)> {
+        self.db
+            .list_with(opts)
+            .into_iter()
+            .map(|mut dir| {
+                let visits = self.visits(&dir.path);
+                if let Cow::Owned(path) = self.db.map_path(&dir.path) {
+                    dir.path = path.into();
+                }
+                (dir, visits)
+            })
+            .collect()
+    }
+
+    pub fn recent(&self, limit: usize, opts: &QueryOptions) -> Vec<String> {
+        self.db
+            .recent(limit, opts)
+            .iter()
+            .map(|dir| self.db.map_path(&dir.path).into_owned())
+            .collect()
+    }
+
+    pub fn random(&self, seed: u64, opts: &QueryOptions) -> Option<String> {
+        let dir = self.db.random(seed, opts)?;
+        Some(self.db.map_path(&dir.path).into_owned())
+    }
+
+    pub fn stats(&self) -> DbStats {
+        self.db.stats()
+    }
+
+    pub fn group_by(&self, depth: usize, opts: &QueryOptions) -> Vec<DirGroup> {
+        self.db.group_by(depth, opts)
+    }
+
+    /// Merge entries from `path`. `columns` lays out a tsv file and is only
+    /// accepted with [`DataFormat::Tsv`]; `delimiter` replaces `|` and is
+    /// only accepted with [`DataFormat::Z`].
+    pub fn import(
+        &mut self,
+        path: &Path,
+        format: DataFormat,
+        columns: Option<&TsvColumns>,
+        delimiter: Option<Delimiter>,
+    ) -> Result<usize> {
+        let count = match (format, columns, delimiter) {
+            (_, Some(_), Some(_)) => bail!("--columns and --delimiter cannot be combined"),
+            (DataFormat::Tsv, Some(columns), None) => self.db.import_tsv(path, columns)?,
+            (_, Some(_), None) => bail!("--columns only applies to --format tsv"),
+            (DataFormat::Z, None, Some(delimiter)) => self.db.import_delimited(path, delimiter)?,
+            (_, None, Some(_)) => bail!("--delimiter only applies to --format z"),
+            (_, None, None) => self.db.import(path, format)?,
+        };
         self.db.save()?;
         Ok(count)
     }
 
-    pub fn export(&self, path: &Path) -> Result<usize> {
-        self.db.export(path)
+    pub fn export(&self, path: &Path, format: DataFormat) -> Result<usize> {
+        self.db.export(path, format)
+    }
+
+    pub fn bookmark(&mut self, name: &str, path: &Path) -> Result<()> {
+        let target = expand_path(path)?;
+        self.db.bookmark(name, &target)
+    }
+
+    pub fn pin(&mut self, path: &Path, pinned: bool) -> Result<()> {
+        let target = expand_path(path)?;
+        self.db.pin(&target.to_string_lossy(), pinned)
+    }
+
+    pub fn purge_missing(&mut self, dry_run: bool) -> Result<Vec<String>> {
+        let missing = self.db.purge_missing(dry_run);
+        self.db.save()?;
+        Ok(missing)
+    }
+
+    /// Remove entries matching the glob `pattern`, after expanding `~` and
+    /// making it absolute, with a single save.
+    pub fn delete_matching(&mut self, pattern: &str, dry_run: bool) -> Result<Vec<String>> {
+        let pattern = expand_path(pattern)?;
+        let matched = self.db.delete_matching(&pattern.to_string_lossy(), dry_run);
+        self.db.save()?;
+        Ok(matched)
+    }
+
+    pub fn compact(&mut self) -> Result<usize> {
+        self.db.compact()
+    }
+
+    pub fn verify(&self) -> Result<DirListDiff> {
+        self.db.verify()
     }
 
     pub fn clear(&mut self) -> Result<()> {
         self.db.clear()?;
         self.db.save()
     }
+
+    /// [`Client::clear`] plus every zcd-owned file next to the datafile
+    /// and the usage counters in `state_dir`.
+    pub fn clear_all(&mut self, state_dir: Option<&Path>) -> Result<()> {
+        self.db.clear_all()?;
+        if let Some(state_dir) = state_dir {
+            usage::clear_usage(state_dir)?;
+        }
+        self.db.save()
+    }
 }
 
 #[cfg(test)]
@@ -57,6 +263,53 @@ mod tests {
     use std::path::PathBuf;
     use tempfile::tempdir;
 
+    #[test]
+    fn go_records_and_returns_the_canonical_path() {
+        let temp_dir = tempdir().unwrap();
+        let config_path = temp_dir.path().join("config");
+        let datafile_path = temp_dir.path().join("zcddata");
+        fs::write(
+            &config_path,
+            format!("datafile={}", datafile_path.display()),
+        )
+        .unwrap();
+        let config = crate::config::load_config_from_path(&config_path).unwrap();
+        let mut client = Client::with_config(config).unwrap();
+        let project = temp_dir.path().join("proj");
+        fs::create_dir(&project).unwrap();
+        let canonical = fs::canonicalize(&project).unwrap();
+
+        let printed = client.go(&project.join("..").join("proj")).unwrap();
+        assert_eq!(printed, canonical.to_str().unwrap());
+        assert_eq!(client.visits(&printed), 1.0);
+        assert!(fs::read_to_string(&datafile_path)
+            .unwrap()
+            .starts_with(&format!("{}|", printed)));
+
+        assert!(client.go(&temp_dir.path().join("missing")).is_err());
+        assert!(client.go(&datafile_path).is_err());
+    }
+
+    #[test]
+    fn clear_all_removes_the_usage_counters() {
+        let temp_dir = tempdir().unwrap();
+        let config_path = temp_dir.path().join("config");
+        let datafile_path = temp_dir.path().join("zcddata");
+        fs::write(
+            &config_path,
+            format!("datafile={}", datafile_path.display()),
+        )
+        .unwrap();
+        let state_dir = temp_dir.path().join("state");
+        usage::record_command_usage(&state_dir, "query").unwrap();
+        let config = crate::config::load_config_from_path(&config_path).unwrap();
+        let mut client = Client::with_config(config).unwrap();
+
+        client.clear_all(Some(&state_dir)).unwrap();
+        assert!(!usage::usage_path(&state_dir).exists());
+        assert!(state_dir.is_dir(), "only the counters file goes");
+    }
+
     // This test creates a temporary config file and data file,
     // then verifies that insert, query, and delete work as expected.
     #[test]
@@ -80,13 +333,14 @@ debug=false "#,
         let entry = temp_dir.path().join("test-entry");
         std::fs::create_dir_all(&entry).unwrap();
         let entry_str = entry.to_str().unwrap();
-        client.insert(entry_str).unwrap();
+        client.insert(entry.as_os_str()).unwrap();
 
-        let query_result = client.query("test");
+        let opts = client.query_options();
+        let query_result = client.query(&["test"], &opts).unwrap();
         assert!(query_result.is_some());
-        assert_eq!(query_result.unwrap().path, entry_str);
+        assert_eq!(query_result.unwrap().dir.path, entry_str);
 
         client.delete(entry_str).unwrap();
-        assert!(client.query("test").is_none());
+        assert!(client.query(&["test"], &opts).unwrap().is_none());
     }
 }
@@ -0,0 +1,546 @@
+//! Display helpers for CLI output.
+//!
+//! Everything here is a pure string transform so the shell contract
+//! (stdout of `query` is the jump target) stays easy to test.
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Error, Result};
+
+use crate::db::dir::{Epoch, Ranking};
+use crate::db::{Dir, DirListDiff};
+
+const MATCH_COLOR: &str = "\x1b[1;32m";
+const RESET: &str = "\x1b[0m";
+
+/// Whether ANSI colors should be written to stdout: only on a TTY and only
+/// when `NO_COLOR` is unset or empty (<https://no-color.org>).
+pub fn color_enabled() -> bool {
+    use_color(
+        env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()),
+        io::stdout().is_terminal(),
+    )
+}
+
+fn use_color(no_color: bool, is_tty: bool) -> bool {
+    is_tty && !no_color
+}
+
+/// Wrap the characters of `dir.path` at `positions` (char indices, as
+/// returned by [`crate::fuzzy::match_positions`]) in ANSI color escapes.
+pub fn highlight(dir: &Dir, positions: &[usize]) -> String {
+    let mut out = String::with_capacity(dir.path.len() + positions.len() * 8);
+    let mut positions = positions.iter().peekable();
+    for (i, ch) in dir.path.chars().enumerate() {
+        if positions.next_if_eq(&&i).is_some() {
+            out.push_str(MATCH_COLOR);
+            out.push(ch);
+            out.push_str(RESET);
+        } else {
+            out.push(ch);
+        }
+    }
+    out
+}
+
+/// Replace `dir.path` with its fully resolved form (symlinks, `.`/`..`).
+/// Display-only: the stored key is untouched, and a path that can no
+/// longer be resolved is shown as stored.
+pub fn canonicalize(mut dir: Dir<'_>) -> Dir<'_> {
+    if let Ok(real) = fs::canonicalize(dir.path.as_ref()) {
+        dir.path = Cow::Owned(real.to_string_lossy().into_owned());
+    }
+    dir
+}
+
+const ELLIPSIS: &str = "...";
+
+/// Shorten `path` to at most `max` characters by replacing its middle with
+/// `...`, keeping both ends (`/home/.../proj`). Counts chars, not bytes, so
+/// multibyte paths are never split inside a character.
+pub fn truncate_middle(path: &str, max: usize) -> String {
+    let len = path.chars().count();
+    if len <= max {
+        return path.to_string();
+    }
+    if max <= ELLIPSIS.len() {
+        return ELLIPSIS[..max].to_string();
+    }
+    let keep = max - ELLIPSIS.len();
+    let tail = keep / 2;
+    let head = keep - tail;
+    let mut out: String = path.chars().take(head).collect();
+    out.push_str(ELLIPSIS);
+    out.extend(path.chars().skip(len - tail));
+    out
+}
+
+/// What the filesystem currently says about a directory, for `--detail`.
+#[derive(Debug, PartialEq)]
+pub struct Detail {
+    pub exists: bool,
+    /// Number of children; `None` when the directory cannot be read.
+    pub children: Option<usize>,
+    /// Modification time; `None` when it cannot be read.
+    pub modified: Option<Epoch>,
+}
+
+/// Stat `path` and count its children. Never fails: anything unreadable
+/// (missing, permission denied) is reported as unknown.
+pub fn inspect(path: &str) -> Detail {
+    let metadata = fs::metadata(path).ok();
+    Detail {
+        exists: metadata.is_some(),
+        children: fs::read_dir(path).ok().map(|entries| entries.count()),
+        modified: metadata
+            .and_then(|m| m.modified().ok())
+            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|d| d.as_secs()),
+    }
+}
+
+impl Detail {
+    /// `3 entries, modified 2h ago`, with `?` for anything unknown.
+    pub fn describe(&self, now: Epoch) -> String {
+        if !self.exists {
+            return "missing".to_string();
+        }
+        let children = self
+            .children
+            .map_or_else(|| "?".to_string(), |n| n.to_string());
+        let modified = self
+            .modified
+            .map_or_else(|| "?".to_string(), |m| relative_time(m, now));
+        format!("{} entries, modified {}", children, modified)
+    }
+}
+
+#[derive(Default)]
+struct TreeNode {
+    /// Set when this node is itself a tracked directory.
+    rank: Option<Ranking>,
+    children: BTreeMap<String, TreeNode>,
+}
+
+/// One `list --watch` frame: clear the screen, home the cursor, then print
+/// `lines`. Clearing everything each time also copes with a resized terminal.
+pub fn redraw(lines: &[String]) -> String {
+    let mut frame = String::from("\x1b[2J\x1b[H");
+    for line in lines {
+        frame.push_str(line);
+        frame.push('\n');
+    }
+    frame
+}
+
+/// Render `dirs` as an indented tree rooted at `/`, two spaces per level,
+/// children sorted by name. Tracked directories are annotated with their
+/// rank. With `depth`, components below that level are not shown.
+pub fn render_tree(dirs: &[Dir], depth: Option<usize>) -> String {
+    let depth = depth.unwrap_or(usize::MAX);
+    let mut root = TreeNode::default();
+    for dir in dirs {
+        let components: Vec<&str> = dir.path.split('/').filter(|c| !c.is_empty()).collect();
+        let mut node = &mut root;
+        for component in components.iter().take(depth) {
+            node = node.children.entry(component.to_string()).or_default();
+        }
+        if components.len() <= depth {
+            node.rank = Some(dir.rank);
+        }
+    }
+
+    fn render(node: &TreeNode, level: usize, out: &mut String) {
+        for (name, child) in &node.children {
+            out.push_str(&"  ".repeat(level));
+            out.push_str(name);
+            if let Some(rank) = child.rank {
+                out.push_str(&format!(" ({:.2})", rank));
+            }
+            out.push('\n');
+            render(child, level + 1, out);
+        }
+    }
+
+    let mut out = String::from("/\n");
+    render(&root, 1, &mut out);
+    out
+}
+
+/// A parsed `--template`: literal text with `{placeholder}` fields and
+/// `\t`/`\n` escapes. Parsing up front rejects unknown placeholders before
+/// anything is printed.
+#[derive(Debug, PartialEq)]
+pub struct Template(Vec<Segment>);
+
+#[derive(Debug, PartialEq)]
+enum Segment {
+    Literal(String),
+    Path,
+    Rank,
+    Visits,
+    LastAccessed,
+    Age,
+}
+
+/// Values substituted for one entry.
+pub struct Fields<'a> {
+    pub path: &'a str,
+    /// Displayed score, as printed by `--rank`.
+    pub rank: Ranking,
+    /// Stored rank, which accumulates one unit per visit.
+    pub visits: Ranking,
+    pub last_accessed: Epoch,
+    pub now: Epoch,
+}
+
+impl FromStr for Template {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut segments = vec![];
+        let mut literal = String::new();
+        let mut chars = s.chars();
+        while let Some(ch) = chars.next() {
+            match ch {
+                '\\' => match chars.next() {
+                    Some('t') => literal.push('\t'),
+                    Some('n') => literal.push('\n'),
+                    Some(other) => literal.push(other),
+                    None => literal.push('\\'),
+                },
+                '{' => {
+                    let mut name = String::new();
+                    let mut closed = false;
+                    for c in chars.by_ref() {
+                        if c == '}' {
+                            closed = true;
+                            break;
+                        }
+                        name.push(c);
+                    }
+                    if !closed {
+                        bail!(
+                            "unterminated placeholder {{{} in template; write \\{{ for a literal brace",
+                            name
+                        );
+                    }
+                    let segment = match name.as_str() {
+                        "path" => Segment::Path,
+                        "rank" => Segment::Rank,
+                        "visits" => Segment::Visits,
+                        "last_accessed" => Segment::LastAccessed,
+                        "age" => Segment::Age,
+                        _ => bail!(
+                            "unknown template placeholder {{{}}}; expected one of \
+                             {{path}}, {{rank}}, {{visits}}, {{last_accessed}}, {{age}}",
+                            name
+                        ),
+                    };
+                    if !literal.is_empty() {
+                        segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                    }
+                    segments.push(segment);
+                }
+                _ => literal.push(ch),
+            }
+        }
+        if !literal.is_empty() {
+            segments.push(Segment::Literal(literal));
+        }
+        if segments.is_empty() {
+            return Err(anyhow!("template is empty"));
+        }
+        Ok(Template(segments))
+    }
+}
+
+impl Template {
+    pub fn render(&self, fields: &Fields) -> String {
+        let mut out = String::new();
+        for segment in &self.0 {
+            match segment {
+                Segment::Literal(text) => out.push_str(text),
+                Segment::Path => out.push_str(fields.path),
+                Segment::Rank => out.push_str(&format!("{:.2}", fields.rank)),
+                Segment::Visits => out.push_str(&format!("{:.1}", fields.visits)),
+                Segment::LastAccessed => out.push_str(&fields.last_accessed.to_string()),
+                Segment::Age => {
+                    out.push_str(&format_age(fields.now.saturating_sub(fields.last_accessed)))
+                }
+            }
+        }
+        out
+    }
+}
+
+/// A `--porcelain` line: path, rank, visits and last access epoch,
+/// tab-separated. Scripts rely on this layout, so it must not follow
+/// changes to the human-readable output; new fields only ever go last.
+pub fn porcelain(fields: &Fields) -> String {
+    format!(
+        "{}\t{:.2}\t{:.1}\t{}",
+        fields.path, fields.rank, fields.visits, fields.last_accessed
+    )
+}
+
+/// How long before `now` the epoch `then` was, e.g. "3d ago".
+pub fn relative_time(then: Epoch, now: Epoch) -> String {
+    format!("{} ago", format_age(now.saturating_sub(then)))
+}
+
+/// One `--ndjson` line: a compact JSON object with the `--porcelain`
+/// fields, without the trailing newline.
+pub fn json_object(fields: &Fields) -> String {
+    format!(
+        "{{\"path\":{},\"rank\":{},\"visits\":{},\"last_accessed\":{}}}",
+        json_string(fields.path),
+        fields.rank,
+        fields.visits,
+        fields.last_accessed
+    )
+}
+
+/// `diff --json` output: one object holding the `added` and `removed`
+/// paths and the `changed` entries with their old and new rank.
+pub fn diff_json(diff: &DirListDiff) -> String {
+    let paths = |paths: &[String]| paths.iter().map(|p| json_string(p)).collect::<Vec<_>>();
+    let changed: Vec<String> = diff
+        .changed
+        .iter()
+        .map(|(path, old, new)| {
+            format!(
+                "{{\"path\":{},\"old_rank\":{:.1},\"new_rank\":{:.1}}}",
+                json_string(path),
+                old,
+                new
+            )
+        })
+        .collect();
+    format!(
+        "{{\"added\":[{}],\"removed\":[{}],\"changed\":[{}]}}",
+        paths(&diff.added).join(","),
+        paths(&diff.removed).join(","),
+        changed.join(",")
+    )
+}
+
+/// `s` as a quoted JSON string.
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Coarse human-readable age: `45s`, `12m`, `3h`, `9d`.
+fn format_age(secs: u64) -> String {
+    match secs {
+        0..=59 => format!("{}s", secs),
+        60..=3599 => format!("{}m", secs / 60),
+        3600..=86399 => format!("{}h", secs / 3600),
+        _ => format!("{}d", secs / 86400),
+    }
+}
+
+#[cfg(test)]
+mod test_format {
+    use super::*;
+
+    fn dir(path: &str) -> Dir<'static> {
+        Dir::new(path.to_string())
+    }
+
+    #[test]
+    fn diff_json_lists_each_kind_of_change() {
+        let diff = DirListDiff {
+            added: vec!["/new \"dir\"".to_string()],
+            removed: vec![],
+            changed: vec![("/proj".to_string(), 2.0, 3.5)],
+        };
+        let value: serde_json::Value = serde_json::from_str(&diff_json(&diff)).unwrap();
+        assert_eq!(value["added"], serde_json::json!(["/new \"dir\""]));
+        assert_eq!(value["removed"], serde_json::json!([]));
+        assert_eq!(
+            value["changed"],
+            serde_json::json!([{"path": "/proj", "old_rank": 2.0, "new_rank": 3.5}])
+        );
+    }
+
+    #[test]
+    fn highlight_wraps_matched_chars() {
+        let d = dir("/home/zcd");
+        let positions = crate::fuzzy::match_positions("zcd", &d.path).unwrap();
+        let out = highlight(&d, &positions);
+        assert_eq!(
+            out,
+            format!("/home/{MATCH_COLOR}z{RESET}{MATCH_COLOR}c{RESET}{MATCH_COLOR}d{RESET}")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn canonicalize_resolves_symlinks() {
+        let tmp = tempfile::tempdir().unwrap();
+        let real = tmp.path().join("real");
+        let link = tmp.path().join("link");
+        fs::create_dir(&real).unwrap();
+        std::os::unix::fs::symlink(&real, &link).unwrap();
+
+        let out = canonicalize(dir(link.to_str().unwrap()));
+        assert_eq!(
+            out.path,
+            fs::canonicalize(&real).unwrap().to_str().unwrap(),
+            "the printed path should be the symlink target"
+        );
+    }
+
+    #[test]
+    fn canonicalize_falls_back_to_stored_path() {
+        let out = canonicalize(dir("/definitely/not/a/real/dir"));
+        assert_eq!(out.path, "/definitely/not/a/real/dir");
+    }
+
+    #[test]
+    fn truncate_middle_keeps_short_paths() {
+        assert_eq!(truncate_middle("/home/proj", 10), "/home/proj");
+        assert_eq!(truncate_middle("/home/proj", 40), "/home/proj");
+    }
+
+    #[test]
+    fn truncate_middle_ellipsizes_long_paths() {
+        let out = truncate_middle("/home/user/projects/work/proj", 16);
+        assert_eq!(out, "/home/u...k/proj");
+        assert_eq!(out.chars().count(), 16);
+    }
+
+    #[test]
+    fn truncate_middle_respects_multibyte_boundaries() {
+        let out = truncate_middle("/用户/文档/项目/很长的目录/路径", 9);
+        assert_eq!(out, "/用户.../路径");
+        assert_eq!(out.chars().count(), 9);
+        assert_eq!(truncate_middle("/用户/文档", 2), "..");
+    }
+
+    #[test]
+    fn inspect_counts_children() {
+        let tmp = tempfile::tempdir().unwrap();
+        fs::create_dir(tmp.path().join("sub")).unwrap();
+        fs::write(tmp.path().join("a"), "").unwrap();
+        fs::write(tmp.path().join(".hidden"), "").unwrap();
+
+        let detail = inspect(tmp.path().to_str().unwrap());
+        assert!(detail.exists);
+        assert_eq!(detail.children, Some(3));
+        let now = detail.modified.unwrap() + 90;
+        assert_eq!(detail.describe(now), "3 entries, modified 1m ago");
+
+        let missing = inspect("/definitely/not/a/real/dir");
+        assert_eq!(missing.children, None);
+        assert_eq!(missing.describe(now), "missing");
+    }
+
+    #[test]
+    fn tree_indents_by_hierarchy_and_marks_tracked_dirs() {
+        let dirs = [
+            dir("/home/u/work/api").with_rank(4.0),
+            dir("/home/u/work").with_rank(2.0),
+            dir("/home/u/play").with_rank(1.5),
+            dir("/etc").with_rank(1.0),
+        ];
+        let expected = [
+            "/",
+            "  etc (1.00)",
+            "  home",
+            "    u",
+            "      play (1.50)",
+            "      work (2.00)",
+            "        api (4.00)",
+        ];
+        assert_eq!(render_tree(&dirs, None), expected.join("\n") + "\n");
+        assert_eq!(
+            render_tree(&dirs, Some(2)),
+            "/\n  etc (1.00)\n  home\n    u\n",
+            "deeper entries are cut off"
+        );
+    }
+
+    #[test]
+    fn template_substitutes_every_placeholder() {
+        let template: Template = "{rank}\\t{path} ({visits} visits, {age} ago, at {last_accessed})"
+            .parse()
+            .unwrap();
+        let out = template.render(&Fields {
+            path: "/home/proj",
+            rank: 12.0,
+            visits: 3.0,
+            last_accessed: 1000,
+            now: 1000 + 2 * 3600 + 5,
+        });
+        assert_eq!(out, "12.00\t/home/proj (3.0 visits, 2h ago, at 1000)");
+    }
+
+    #[test]
+    fn porcelain_fields_are_tab_separated_in_a_fixed_order() {
+        let line = porcelain(&Fields {
+            path: "/home/my proj",
+            rank: 12.345,
+            visits: 3.0,
+            last_accessed: 1_600_000_000,
+            now: 1_700_000_000,
+        });
+        assert_eq!(line, "/home/my proj\t12.35\t3.0\t1600000000");
+    }
+
+    #[test]
+    fn relative_time_switches_units_at_each_boundary() {
+        let now = 1_000_000;
+        assert_eq!(relative_time(now, now), "0s ago");
+        assert_eq!(relative_time(now - 59, now), "59s ago");
+        assert_eq!(relative_time(now - 60, now), "1m ago");
+        assert_eq!(relative_time(now - 3599, now), "59m ago");
+        assert_eq!(relative_time(now - 3600, now), "1h ago");
+        assert_eq!(relative_time(now - 86399, now), "23h ago");
+        assert_eq!(relative_time(now - 86400, now), "1d ago");
+        assert_eq!(relative_time(now - 3 * 86400 - 5, now), "3d ago");
+        assert_eq!(relative_time(now + 10, now), "0s ago", "future times clamp");
+    }
+
+    #[test]
+    fn template_rejects_unknown_placeholders() {
+        let err = "{path} {size}".parse::<Template>().unwrap_err();
+        assert!(err.to_string().contains("{size}"), "got: {err}");
+        assert!("".parse::<Template>().is_err());
+        for unterminated in ["{path", "size: {"] {
+            let err = unterminated.parse::<Template>().unwrap_err();
+            assert!(
+                err.to_string().contains("unterminated placeholder"),
+                "{unterminated}: {err}"
+            );
+        }
+        assert!("\\{path".parse::<Template>().is_ok());
+    }
+
+    #[test]
+    fn no_color_disables_escapes() {
+        assert!(use_color(false, true));
+        assert!(!use_color(true, true), "NO_COLOR must disable escapes");
+        assert!(!use_color(false, false), "non-TTY must not get escapes");
+    }
+}
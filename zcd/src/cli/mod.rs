@@ -1,16 +1,36 @@
+mod boot;
 mod client;
+mod format;
+mod self_test;
 
 use anyhow::{bail, Context, Result};
 use client::Client;
 
-use crate::config::generate_config_file;
+use crate::config::{config_files, generate_config_file, load_config_from_paths, state_dir};
+use crate::db::dir::now;
+use crate::db::{
+    diff_files, merge_files, usage, DataFormat, Delimiter, Origin, QueryOptions, ScoredDir,
+    TsvColumns,
+};
+use format::{Fields, Template};
+use itertools::Itertools;
 
 use clap::{Args, Parser, Subcommand};
-use std::path::PathBuf;
+use std::ffi::OsString;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
 
 /// zcd – a simple jump navigation CLI tool.
 #[derive(Debug, Parser)]
-#[clap(name="zcd", author, about="zcd CLI tool", long_about = None)]
+#[clap(
+    name = "zcd",
+    author,
+    about = "zcd CLI tool",
+    long_about = None,
+    after_help = "Run `zcd examples` for shell integration snippets."
+)]
 pub struct Cli {
     #[clap(subcommand)]
     pub command: Commands,
@@ -22,43 +42,276 @@ pub struct Cli {
 pub enum Commands {
     /// insert or update an entry
     #[clap(arg_required_else_help = true)]
-    Insert { entry: String },
+    Insert(InsertArgs),
+    /// record a visit to a directory and print its canonical path for `cd`
+    #[clap(arg_required_else_help = true)]
+    Go { path: PathBuf },
     /// delete an entry
     #[clap(arg_required_else_help = true)]
     Delete { entry: String },
+    /// delete every entry whose path matches a glob such as `~/scratch/*`
+    #[clap(arg_required_else_help = true)]
+    DeleteAll {
+        /// `*` and `?` stay within a path component, `**` crosses them
+        pattern: String,
+        /// list what would be removed without changing anything
+        #[clap(long)]
+        dry_run: bool,
+        /// do not ask for confirmation; required when not run from a terminal
+        #[clap(long, short)]
+        yes: bool,
+    },
     /// query an entry based on keyword
     #[clap(arg_required_else_help = true)]
     Query(QueryArgs),
+    /// print the best match for a keyword without recording anything
+    #[clap(arg_required_else_help = true)]
+    Which { pattern: String },
     /// list all entries
     List(ListArgs),
-    /// merge entries from a z-compatible datafile
+    /// merge entries from an exported or z-compatible datafile
+    #[clap(arg_required_else_help = true)]
+    Import {
+        path: PathBuf,
+        /// line format of the file: z (path|rank|last), kv or tsv
+        #[clap(long, default_value = "z")]
+        format: DataFormat,
+        /// tsv column order, e.g. last_accessed,path,visits; `_` skips a column
+        #[clap(long)]
+        columns: Option<TsvColumns>,
+        /// field separator of a z-format file instead of `|`: one character,
+        /// or `tab`
+        #[clap(long)]
+        delimiter: Option<Delimiter>,
+    },
+    /// merge datafiles into a new file without touching the live database
     #[clap(arg_required_else_help = true)]
-    Import { path: PathBuf },
-    /// write all entries to a z-compatible datafile
+    Merge {
+        /// datafiles to merge; shared paths keep the higher rank and latest visit
+        #[clap(required = true, min_values = 2)]
+        inputs: Vec<PathBuf>,
+        /// where to write the merged entries
+        #[clap(short, long)]
+        output: PathBuf,
+        /// line format of the inputs and output: z (path|rank|last) or kv
+        #[clap(long, default_value = "z")]
+        format: DataFormat,
+    },
+    /// show entries added, removed or re-ranked between two datafiles
     #[clap(arg_required_else_help = true)]
-    Export { path: PathBuf },
+    Diff {
+        old: PathBuf,
+        new: PathBuf,
+        /// line format of both files: z (path|rank|last), kv or tsv
+        #[clap(long, default_value = "z")]
+        format: DataFormat,
+        /// print one JSON object with added, removed and changed entries
+        #[clap(long)]
+        json: bool,
+    },
+    /// write all entries to a file (z-compatible by default)
+    #[clap(arg_required_else_help = true)]
+    Export {
+        path: PathBuf,
+        /// line format of the file: z (path|rank|last), kv or tsv (sorted by path)
+        #[clap(long, default_value = "z")]
+        format: DataFormat,
+    },
+    /// list the most recently visited directories, newest first
+    Recent {
+        /// how many to show
+        #[clap(default_value = "10")]
+        count: usize,
+    },
+    /// print a random directory, favoring higher-ranked ones
+    Random {
+        /// seed for a reproducible pick; defaults to the current time
+        #[clap(long)]
+        seed: Option<u64>,
+    },
+    /// summarize the database
+    Stats,
+    /// print local per-command usage counters (see the usage_stats config)
+    Usage,
+    /// show tracked directories as an indented tree
+    Tree {
+        /// only show the first N path components
+        #[clap(long, value_name = "N")]
+        depth: Option<usize>,
+    },
+    /// compare loaded entries with the datafile on disk
+    Verify,
+    /// rewrite the datafile in canonical form
+    Compact,
+    /// remove entries whose directory no longer exists
+    PurgeMissing {
+        /// list what would be removed without changing anything
+        #[clap(long)]
+        dry_run: bool,
+    },
     /// config management
     #[clap(arg_required_else_help = true)]
     Config(ConfigArgs),
+    /// name a directory so `query @name` jumps straight to it
+    Bookmark { name: String, path: PathBuf },
+    /// keep an entry from ever being decayed, aged out or purged
+    #[clap(arg_required_else_help = true)]
+    Pin { path: PathBuf },
+    /// let a pinned entry age and be purged again
+    #[clap(arg_required_else_help = true)]
+    Unpin { path: PathBuf },
     /// clear all history
-    Clear,
+    Clear(ClearArgs),
+    /// check insert, query and delete against a throwaway datafile
+    SelfTest,
+    /// print shell integration snippets for zsh, bash and fish
+    Examples,
     /// display version information
     Version,
 }
 
+#[derive(Debug, Args)]
+pub struct InsertArgs {
+    #[clap(
+        required_unless_present = "from-file",
+        parse(from_os_str),
+        allow_invalid_utf8 = true
+    )]
+    entry: Option<OsString>,
+    /// insert one path per line from a file (`-` for stdin)
+    #[clap(long, conflicts_with = "entry")]
+    from_file: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub struct ClearArgs {
+    /// also remove bookmarks, backups and other zcd files next to the datafile, and the usage counters
+    #[clap(long)]
+    all: bool,
+    /// do not ask for confirmation; required when not run from a terminal
+    #[clap(long, short)]
+    yes: bool,
+}
+
 #[derive(Debug, Args)]
 pub struct ListArgs {
     /// show rank
     #[clap(short, long)]
     rank: bool,
+    /// shorten displayed paths to N characters with a middle ellipsis
+    #[clap(long, value_name = "N")]
+    truncate: Option<usize>,
+    /// only show entries visited at least N times
+    #[clap(long, value_name = "N")]
+    min_visits: Option<u32>,
+    /// lowest rank first
+    #[clap(long)]
+    reverse: bool,
+    /// only show entries visited since the machine booted
+    #[clap(long)]
+    since_boot: bool,
+    /// hide entries whose path contains PATTERN (repeatable)
+    #[clap(long, value_name = "PATTERN", multiple_occurrences = true)]
+    exclude: Vec<String>,
+    /// only show entries that were imported and never visited (imported) or
+    /// the rest (visited)
+    #[clap(long)]
+    origin: Option<Origin>,
+    /// print entry count and summed rank per group of the first N path components
+    #[clap(long, value_name = "N")]
+    group_by: Option<usize>,
+    /// format each entry, e.g. "{rank}\t{path}"; placeholders: {path}, {rank},
+    /// {visits}, {last_accessed}, {age}
+    #[clap(long)]
+    template: Option<Template>,
+    /// show at most N entries
+    #[clap(long, value_name = "N")]
+    limit: Option<usize>,
+    /// clear the screen and redraw the list every --interval seconds
+    #[clap(long)]
+    watch: bool,
+    /// print "path<TAB>rank<TAB>visits<TAB>last_accessed" lines, a layout that
+    /// stays stable across releases
+    #[clap(long, conflicts_with_all = &["template", "truncate", "group-by"])]
+    porcelain: bool,
+    /// print one JSON object per line with the --porcelain fields, for jq
+    #[clap(
+        long,
+        conflicts_with_all = &["template", "truncate", "group-by", "porcelain", "watch"]
+    )]
+    ndjson: bool,
+    /// append how long ago each entry was visited, e.g. "2h ago"
+    #[clap(long, conflicts_with_all = &["template", "porcelain", "group-by"])]
+    relative: bool,
+    /// append each entry's last visit as a Unix timestamp
+    #[clap(long, conflicts_with_all = &["template", "porcelain", "group-by", "relative"])]
+    epoch: bool,
+    /// seconds between redraws in --watch mode, at least 1
+    #[clap(
+        long,
+        value_name = "SECS",
+        default_value = "2",
+        requires = "watch",
+        parse(try_from_str = parse_interval)
+    )]
+    interval: u64,
 }
 
 #[derive(Debug, Args)]
 pub struct QueryArgs {
-    entry: String,
+    /// keywords that must all match the path
+    #[clap(required = true)]
+    entry: Vec<String>,
     /// show rank
     #[clap(short, long)]
     rank: bool,
+    /// highlight matched characters (TTY only, respects NO_COLOR)
+    #[clap(long)]
+    color: bool,
+    /// print the fully resolved path (symlinks, `.` and `..`)
+    #[clap(long, visible_alias = "absolute")]
+    canonical: bool,
+    /// skip this many ranked matches
+    #[clap(long, default_value_t = 0)]
+    offset: usize,
+    /// print up to this many matches, one per line
+    #[clap(long)]
+    limit: Option<usize>,
+    /// only consider entries visited at least N times
+    #[clap(long, value_name = "N")]
+    min_visits: Option<u32>,
+    /// record a visit to the best match, like jumping to it with z, even
+    /// when --offset shows later matches
+    #[clap(long)]
+    bump: bool,
+    /// return matches without checking that they still exist
+    #[clap(long)]
+    no_existence_check: bool,
+    /// format each match like `list --template`
+    #[clap(long)]
+    template: Option<Template>,
+    /// append each match's child count and modification time
+    #[clap(long, conflicts_with = "template")]
+    detail: bool,
+    /// skip matches whose path contains PATTERN (repeatable)
+    #[clap(long, value_name = "PATTERN", multiple_occurrences = true)]
+    exclude: Vec<String>,
+    /// print only the number of matches
+    #[clap(long, conflicts_with_all = &["bump", "template", "detail"])]
+    count: bool,
+    /// print "path<TAB>rank<TAB>visits<TAB>last_accessed" lines, a layout that
+    /// stays stable across releases
+    #[clap(
+        long,
+        conflicts_with_all = &["template", "detail", "color", "canonical", "count"]
+    )]
+    porcelain: bool,
+    /// when nothing matches, print the pattern itself if it is a directory
+    #[clap(long, conflicts_with = "count")]
+    fallback_literal: bool,
+    /// fuzzy match even when the keyword is a tracked path (see `exact_path`)
+    #[clap(long)]
+    no_exact: bool,
 }
 
 #[derive(Debug, Args)]
@@ -67,68 +320,416 @@ pub struct ConfigArgs {
     /// generate default config file
     #[clap(long, short)]
     generate: bool,
+    /// with --generate, print the default config instead of writing it
+    #[clap(long, requires = "generate")]
+    dry_run: bool,
+    /// print the effective configuration and where each value came from
+    #[clap(long, short)]
+    show: bool,
 }
 
 pub trait AppExt {
     fn run(&self) -> Result<()>;
 }
 
+impl Commands {
+    /// Subcommand name as typed on the command line.
+    fn name(&self) -> &'static str {
+        match self {
+            Commands::Insert(_) => "insert",
+            Commands::Go { .. } => "go",
+            Commands::Delete { .. } => "delete",
+            Commands::DeleteAll { .. } => "delete-all",
+            Commands::Query(_) => "query",
+            Commands::Which { .. } => "which",
+            Commands::List(_) => "list",
+            Commands::Import { .. } => "import",
+            Commands::Export { .. } => "export",
+            Commands::Merge { .. } => "merge",
+            Commands::Diff { .. } => "diff",
+            Commands::Recent { .. } => "recent",
+            Commands::Random { .. } => "random",
+            Commands::Stats => "stats",
+            Commands::Usage => "usage",
+            Commands::Tree { .. } => "tree",
+            Commands::Verify => "verify",
+            Commands::Compact => "compact",
+            Commands::PurgeMissing { .. } => "purge-missing",
+            Commands::Config(_) => "config",
+            Commands::Bookmark { .. } => "bookmark",
+            Commands::Pin { .. } => "pin",
+            Commands::Unpin { .. } => "unpin",
+            Commands::Clear(_) => "clear",
+            Commands::SelfTest => "self-test",
+            Commands::Examples => "examples",
+            Commands::Version => "version",
+        }
+    }
+}
+
+impl Cli {
+    /// Count this run when `usage_stats` is enabled. Best effort: a broken
+    /// config or unwritable counter file never fails the command itself.
+    fn record_usage(&self) {
+        let Ok(config) = load_config_from_paths(&config_files()) else {
+            return;
+        };
+        if !config.usage_stats {
+            return;
+        }
+        let Some(state_dir) = state_dir() else {
+            return;
+        };
+        if let Err(e) = usage::record_command_usage(&state_dir, self.command.name()) {
+            if self.verbose {
+                eprintln!("failed to record usage: {:#}", e);
+            }
+        }
+    }
+}
+
 impl AppExt for Cli {
     fn run(&self) -> Result<()> {
+        self.record_usage();
         match &self.command {
-            Commands::Clear => {
+            Commands::Clear(args) => {
                 let mut client = Client::new().context("failed to create client")?;
-                client.clear()?;
+                let interactive = io::stdin().is_terminal() && io::stdout().is_terminal();
+                let count = client.stats().entries;
+                if !confirm_removal(
+                    count,
+                    args.yes,
+                    interactive,
+                    io::stdin().lock(),
+                    io::stdout(),
+                )? {
+                    println!("Nothing was cleared.");
+                    return Ok(());
+                }
+                if args.all {
+                    client.clear_all(state_dir().as_deref())?;
+                } else {
+                    client.clear()?;
+                }
                 println!("All entries have been cleared.");
             }
-            Commands::Insert { entry } => {
+            Commands::Insert(args) => {
                 let mut client = Client::new().context("failed to create client")?;
-                client.insert(entry)?;
+                match (&args.entry, &args.from_file) {
+                    (_, Some(list)) => {
+                        client.insert_from_file(list)?;
+                    }
+                    (Some(entry), None) => client.insert(entry)?,
+                    (None, None) => unreachable!("clap requires an entry or --from-file"),
+                }
+            }
+            Commands::Go { path } => {
+                let mut client = Client::new().context("failed to create client")?;
+                println!("{}", client.go(path)?);
             }
             Commands::Delete { entry } => {
                 let mut client = Client::new().context("failed to create client")?;
                 client.delete(entry)?;
             }
+            Commands::DeleteAll {
+                pattern,
+                dry_run,
+                yes,
+            } => {
+                let mut client = Client::new().context("failed to create client")?;
+                let matched = client.delete_matching(pattern, true)?;
+                for path in &matched {
+                    println!("{}", path);
+                }
+                if *dry_run {
+                    println!("would remove {} entries", matched.len());
+                    return Ok(());
+                }
+                if matched.is_empty() {
+                    return Ok(());
+                }
+                let interactive = io::stdin().is_terminal() && io::stdout().is_terminal();
+                if !confirm_removal(
+                    matched.len(),
+                    *yes,
+                    interactive,
+                    io::stdin().lock(),
+                    io::stdout(),
+                )? {
+                    println!("Nothing was deleted.");
+                    return Ok(());
+                }
+                let removed = client.delete_matching(pattern, false)?;
+                println!("removed {} entries", removed.len());
+            }
             Commands::Query(args) => {
-                let client = Client::new().context("failed to create client")?;
-                match client.query(&args.entry) {
-                    Some(dir) => {
-                        if args.rank {
-                            println!("{:.2} {}", dir.rank, dir);
-                        } else {
-                            println!("{}", dir);
-                        }
+                let mut client = Client::new().context("failed to create client")?;
+                let opts = query_options(&client, args);
+                let terms: Vec<&str> = args.entry.iter().map(String::as_str).collect();
+                if args.count {
+                    println!("{}", client.count(&terms, &opts));
+                    return Ok(());
+                }
+                let dirs = query_matches(&client, args, self.verbose)?;
+                if dirs.is_empty() {
+                    if let Some(dir) = unmatched_target(&mut client, args)? {
+                        println!("{}", dir);
+                        return Ok(());
                     }
                     // Keep stdout clean: the shell plugin consumes stdout
                     // as the jump target.
-                    None => bail!("no match found for {}", args.entry),
+                    bail!("no match found for {}", args.entry.join(" "));
+                }
+                let now = now();
+                for scored in dirs {
+                    let dir = scored.dir;
+                    if args.porcelain {
+                        println!(
+                            "{}",
+                            format::porcelain(&Fields {
+                                path: &dir.path,
+                                rank: dir.rank,
+                                visits: scored.visits,
+                                last_accessed: dir.last_accessed,
+                                now,
+                            })
+                        );
+                        continue;
+                    }
+                    if let Some(template) = &args.template {
+                        let visits = scored.visits;
+                        let shown = if args.canonical {
+                            format::canonicalize(dir.clone())
+                        } else {
+                            dir.clone()
+                        };
+                        println!(
+                            "{}",
+                            template.render(&Fields {
+                                path: &shown.path,
+                                rank: dir.rank,
+                                visits,
+                                last_accessed: dir.last_accessed,
+                                now,
+                            })
+                        );
+                        continue;
+                    }
+                    let path = if args.canonical {
+                        format::canonicalize(dir.clone()).to_string()
+                    } else if args.color && format::color_enabled() {
+                        format::highlight(&dir, &scored.positions)
+                    } else {
+                        dir.to_string()
+                    };
+                    let path = if args.detail {
+                        format!("{}\t{}", path, format::inspect(&dir.path).describe(now))
+                    } else {
+                        path
+                    };
+                    if args.rank {
+                        println!("{:.2} {}", dir.rank, path);
+                    } else {
+                        println!("{}", path);
+                    }
+                }
+                if args.bump {
+                    client.bump(&terms, &opts)?;
                 }
             }
-            Commands::Import { path } => {
+            Commands::Which { pattern } => {
+                let client = Client::new().context("failed to create client")?;
+                println!("{}", which(&client, pattern)?);
+            }
+            Commands::Bookmark { name, path } => {
+                let mut client = Client::new().context("failed to create client")?;
+                client.bookmark(name, path)?;
+            }
+            Commands::Pin { path } => {
+                let mut client = Client::new().context("failed to create client")?;
+                client.pin(path, true)?;
+            }
+            Commands::Unpin { path } => {
+                let mut client = Client::new().context("failed to create client")?;
+                client.pin(path, false)?;
+            }
+            Commands::Import {
+                path,
+                format,
+                columns,
+                delimiter,
+            } => {
                 let mut client = Client::new().context("failed to create client")?;
-                let count = client.import(path)?;
+                let count = client.import(path, *format, columns.as_ref(), *delimiter)?;
                 println!("imported {} entries from {}", count, path.display());
             }
-            Commands::Export { path } => {
+            Commands::Merge {
+                inputs,
+                output,
+                format,
+            } => {
+                let count = merge_files(inputs, output, *format)?;
+                println!("merged {} entries into {}", count, output.display());
+            }
+            Commands::Diff {
+                old,
+                new,
+                format,
+                json,
+            } => {
+                let diff = diff_files(old, new, *format)?;
+                if *json {
+                    println!("{}", format::diff_json(&diff));
+                    return Ok(());
+                }
+                for path in &diff.added {
+                    println!("+ {}", path);
+                }
+                for path in &diff.removed {
+                    println!("- {}", path);
+                }
+                for (path, old_rank, new_rank) in &diff.changed {
+                    println!("~ {} ({:.1} -> {:.1})", path, old_rank, new_rank);
+                }
+            }
+            Commands::Export { path, format } => {
                 let client = Client::new().context("failed to create client")?;
-                let count = client.export(path)?;
+                let count = client.export(path, *format)?;
                 println!("exported {} entries to {}", count, path.display());
             }
             Commands::List(list_args) => {
-                let client = Client::new().context("failed to create client")?;
-                for dir in client.list() {
-                    if list_args.rank {
-                        println!("{:.2} {}", dir.rank, dir);
-                    } else {
-                        println!("{}", dir);
+                if list_args.ndjson {
+                    let client = Client::new().context("failed to create client")?;
+                    write_ndjson(&client, list_args, io::BufWriter::new(io::stdout().lock()))?;
+                    return Ok(());
+                }
+                if !list_args.watch {
+                    let client = Client::new().context("failed to create client")?;
+                    for line in list_lines(&client, list_args)? {
+                        println!("{}", line);
                     }
+                    return Ok(());
                 }
+                // Reopen the database every tick so visits recorded by other
+                // shells show up; Ctrl-C ends the loop.
+                loop {
+                    let client = Client::new().context("failed to create client")?;
+                    print!("{}", format::redraw(&list_lines(&client, list_args)?));
+                    io::stdout().flush()?;
+                    thread::sleep(Duration::from_secs(list_args.interval));
+                }
+            }
+            Commands::Recent { count } => {
+                let client = Client::new().context("failed to create client")?;
+                for path in client.recent(*count, &client.query_options()) {
+                    println!("{}", path);
+                }
+            }
+            Commands::Random { seed } => {
+                let client = Client::new().context("failed to create client")?;
+                let seed = seed.unwrap_or_else(|| {
+                    let elapsed = SystemTime::now()
+                        .duration_since(SystemTime::UNIX_EPOCH)
+                        .unwrap_or_default();
+                    elapsed.as_secs() ^ u64::from(elapsed.subsec_nanos())
+                });
+                match client.random(seed, &client.query_options()) {
+                    Some(path) => println!("{}", path),
+                    None => bail!("no entries to pick from"),
+                }
+            }
+            Commands::Stats => {
+                let client = Client::new().context("failed to create client")?;
+                let stats = client.stats();
+                let epoch = |t: Option<u64>| t.map_or_else(|| "-".to_string(), |t| t.to_string());
+                println!("entries: {}", stats.entries);
+                println!("total visits: {:.1}", stats.total_visits);
+                println!(
+                    "rank: min {:.1}, max {:.1}, mean {:.2}",
+                    stats.min_rank, stats.max_rank, stats.mean_rank
+                );
+                println!("oldest access: {}", epoch(stats.oldest));
+                println!("newest access: {}", epoch(stats.newest));
+                println!("missing: {}", stats.missing);
+            }
+            Commands::Usage => {
+                let config =
+                    load_config_from_paths(&config_files()).context("failed to load config")?;
+                if !config.usage_stats {
+                    eprintln!("usage counting is disabled; set usage_stats=true to enable it");
+                }
+                let state_dir = state_dir().context("cannot resolve the state directory")?;
+                let counts = usage::command_usage(&state_dir)?;
+                for (name, count) in counts.iter().sorted_by(|a, b| b.1.cmp(a.1)) {
+                    println!("{} {}", count, name);
+                }
+            }
+            Commands::Tree { depth } => {
+                let client = Client::new().context("failed to create client")?;
+                let dirs = client.list(&client.query_options());
+                print!("{}", format::render_tree(&dirs, *depth));
+            }
+            Commands::Compact => {
+                let mut client = Client::new().context("failed to create client")?;
+                let count = client.compact()?;
+                println!("compacted {} entries", count);
+            }
+            Commands::PurgeMissing { dry_run } => {
+                let mut client = Client::new().context("failed to create client")?;
+                let missing = client.purge_missing(*dry_run)?;
+                for path in &missing {
+                    println!("{}", path);
+                }
+                if *dry_run {
+                    println!("would remove {} missing entries", missing.len());
+                } else {
+                    println!("removed {} missing entries", missing.len());
+                }
+            }
+            Commands::Verify => {
+                let client = Client::new().context("failed to create client")?;
+                let diff = client.verify()?;
+                for path in &diff.added {
+                    println!("only in memory: {}", path);
+                }
+                for path in &diff.removed {
+                    println!("only on disk: {}", path);
+                }
+                for (path, disk, memory) in &diff.changed {
+                    println!(
+                        "rank differs: {} (disk {:.1}, memory {:.1})",
+                        path, disk, memory
+                    );
+                }
+                if !diff.is_empty() {
+                    bail!("in-memory entries diverge from the datafile");
+                }
+                println!("in sync");
             }
             Commands::Config(config) => {
                 if config.generate {
-                    generate_config_file()?;
+                    generate_config_file(config.dry_run)?;
+                }
+                if config.show {
+                    let files = config_files();
+                    for file in &files {
+                        println!("# {}", file.display());
+                    }
+                    let resolved =
+                        load_config_from_paths(&files).context("failed to load config")?;
+                    for (key, value, source) in resolved.entries() {
+                        println!("{}={}  # {}", key, value, source);
+                    }
                 }
             }
+            Commands::Examples => print!("{}", EXAMPLES),
+            Commands::SelfTest => match self_test::run() {
+                Ok(()) => println!("PASS"),
+                Err(e) => {
+                    println!("FAIL");
+                    return Err(e.context("self-test failed"));
+                }
+            },
             Commands::Version => {
                 println!("zcd version {}", env!("CARGO_PKG_VERSION"));
             }
@@ -136,3 +737,647 @@ impl AppExt for Cli {
         Ok(())
     }
 }
+
+/// A `--interval` of whole seconds; zero would redraw in a busy loop.
+fn parse_interval(s: &str) -> Result<u64, String> {
+    match s.parse::<u64>() {
+        Ok(0) => Err("must be at least 1".to_string()),
+        Ok(secs) => Ok(secs),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Query options for the `query` filters in `args`.
+fn query_options(client: &Client, args: &QueryArgs) -> QueryOptions {
+    let mut opts = client.query_options();
+    if let Some(min) = args.min_visits {
+        opts.min_rank = min.into();
+    }
+    opts.skip_existence_check = args.no_existence_check;
+    opts.exclude = args.exclude.clone();
+    if args.no_exact {
+        opts.exact_path = false;
+    }
+    opts
+}
+
+/// The matches `zcd query` prints. Without `--offset`/`--limit` this is
+/// the single jump target from [`Client::query`], the same one `which`
+/// picks; otherwise the requested page of ranked matches.
+fn query_matches<'c>(
+    client: &'c Client,
+    args: &QueryArgs,
+    verbose: bool,
+) -> Result<Vec<ScoredDir<'c>>> {
+    let opts = query_options(client, args);
+    let terms: Vec<&str> = args.entry.iter().map(String::as_str).collect();
+    Ok(match (args.offset, args.limit) {
+        (0, None) => client.query(&terms, &opts)?.into_iter().collect(),
+        (offset, limit) => {
+            let page = client.query_page(&terms, offset, limit.unwrap_or(1), &opts);
+            if verbose {
+                eprintln!(
+                    "showing {} of {} matches from offset {}",
+                    page.dirs.len(),
+                    page.total,
+                    offset
+                );
+            }
+            page.dirs
+        }
+    })
+}
+
+/// The path `zcd which` prints. Read-only: nothing is recorded or saved.
+fn which(client: &Client, pattern: &str) -> Result<String> {
+    match client.resolve(pattern)? {
+        Some(path) => Ok(path),
+        None => bail!("no match found for {}", pattern),
+    }
+}
+
+/// Query options for the `list` filters in `args`.
+fn list_options(client: &Client, args: &ListArgs) -> Result<QueryOptions> {
+    let mut opts = client.query_options();
+    if let Some(min) = args.min_visits {
+        opts.min_rank = min.into();
+    }
+    opts.exclude = args.exclude.clone();
+    opts.origin = args.origin;
+    if args.since_boot {
+        opts.accessed_since =
+            boot::boot_time().context("cannot determine boot time on this platform")?;
+    }
+    Ok(opts)
+}
+
+/// Write `list --ndjson` output to `out` one entry at a time rather than
+/// building it up first. Returns the number of lines written.
+fn write_ndjson<W: Write>(client: &Client, args: &ListArgs, mut out: W) -> Result<usize> {
+    let mut dirs = client.list_with_visits(&list_options(client, args)?);
+    if args.reverse {
+        dirs.reverse();
+    }
+    let now = now();
+    let mut count = 0;
+    for (dir, visits) in dirs.iter().take(args.limit.unwrap_or(usize::MAX)) {
+        let fields = Fields {
+            path: &dir.path,
+            rank: dir.rank,
+            visits: *visits,
+            last_accessed: dir.last_accessed,
+            now,
+        };
+        writeln!(out, "{}", format::json_object(&fields))?;
+        count += 1;
+    }
+    out.flush()?;
+    Ok(count)
+}
+
+/// The lines `zcd list` prints for `args`, one per entry or group.
+fn list_lines(client: &Client, args: &ListArgs) -> Result<Vec<String>> {
+    let opts = list_options(client, args)?;
+    let limit = args.limit.unwrap_or(usize::MAX);
+    if let Some(depth) = args.group_by {
+        let mut groups = client.group_by(depth, &opts);
+        if args.reverse {
+            groups.reverse();
+        }
+        return Ok(groups
+            .into_iter()
+            .take(limit)
+            .map(|group| format!("{:.2} {} {}", group.rank, group.count, group.prefix))
+            .collect());
+    }
+    let mut dirs = client.list_with_visits(&opts);
+    if args.reverse {
+        dirs.reverse();
+    }
+    let now = now();
+    Ok(dirs
+        .into_iter()
+        .take(limit)
+        .map(|(dir, visits)| {
+            let path = match args.truncate {
+                Some(max) => format::truncate_middle(&dir.path, max),
+                None => dir.to_string(),
+            };
+            let fields = Fields {
+                path: &path,
+                rank: dir.rank,
+                visits,
+                last_accessed: dir.last_accessed,
+                now,
+            };
+            if args.porcelain {
+                format::porcelain(&fields)
+            } else if let Some(template) = &args.template {
+                template.render(&fields)
+            } else {
+                let line = if args.rank {
+                    format!("{:.2} {}", dir.rank, path)
+                } else {
+                    path
+                };
+                if args.relative {
+                    format!(
+                        "{}\t{}",
+                        line,
+                        format::relative_time(dir.last_accessed, now)
+                    )
+                } else if args.epoch {
+                    format!("{}\t{}", line, dir.last_accessed)
+                } else {
+                    line
+                }
+            }
+        })
+        .collect())
+}
+
+/// Whether `clear` or `delete-all` may go ahead with removing `count`
+/// entries: always with `yes`, after a y/N prompt on a terminal, and never
+/// otherwise, so a stray command in a script cannot wipe the history.
+fn confirm_removal<R: BufRead, W: Write>(
+    count: usize,
+    yes: bool,
+    interactive: bool,
+    mut input: R,
+    mut out: W,
+) -> Result<bool> {
+    if yes {
+        return Ok(true);
+    }
+    if !interactive {
+        bail!("refusing to delete {} entries without --yes", count);
+    }
+    write!(out, "This will delete {} entries. Continue? (y/N): ", count)?;
+    out.flush()?;
+    let mut answer = String::new();
+    input.read_line(&mut answer)?;
+    Ok(answer.trim().eq_ignore_ascii_case("y"))
+}
+
+/// Printed by `zcd examples`: record every directory change with
+/// `zcd insert`, and `cd` to what `zcd query` prints.
+const EXAMPLES: &str = r#"# zsh: source the bundled plugin, which defines z and zi
+source /path/to/zcd/scripts/zcd.plugin.zsh
+
+# zsh, by hand: record each directory change and jump with z
+chpwd() { zcd insert -- "$PWD" }
+z() { local dir; dir="$(zcd query -- "$@")" && cd "$dir" }
+
+# bash: record from the prompt, since bash has no chpwd hook
+PROMPT_COMMAND="zcd insert -- \"\$PWD\"${PROMPT_COMMAND:+; $PROMPT_COMMAND}"
+z() { local dir; dir="$(zcd query -- "$@")" && cd "$dir"; }
+
+# fish: record on every change of $PWD
+function __zcd_insert --on-variable PWD; zcd insert -- $PWD; end
+function z; set -l dir (zcd query -- $argv); and cd $dir; end
+
+# any shell: record a visit and cd in one call
+cd "$(zcd go ~/projects/app)"
+
+# pick interactively with fzf
+cd "$(zcd list | fzf)"
+"#;
+
+/// Where `zcd query` goes when [`query_matches`] found nothing: the new
+/// name of a renamed directory, else the `--fallback-literal` pattern.
+fn unmatched_target(client: &mut Client, args: &QueryArgs) -> Result<Option<String>> {
+    let opts = query_options(client, args);
+    let terms: Vec<&str> = args.entry.iter().map(String::as_str).collect();
+    if let Some(dir) = client.follow_rename(&terms, &opts)? {
+        return Ok(Some(dir));
+    }
+    Ok(literal_fallback(&args.entry, args.fallback_literal).map(str::to_string))
+}
+
+/// Where `query --fallback-literal` sends the shell when nothing matched:
+/// the pattern itself, so `z foo` behaves like `cd foo` for a directory
+/// zcd has never seen.
+fn literal_fallback(terms: &[String], enabled: bool) -> Option<&str> {
+    match terms {
+        [term] if enabled && Path::new(term).is_dir() => Some(term),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::load_config_from_path;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn watch_frame_redraws_the_limited_list() {
+        let temp_dir = tempdir().unwrap();
+        let datafile = temp_dir.path().join("zcddata");
+        let config_path = temp_dir.path().join("config");
+        fs::write(&config_path, format!("datafile={}", datafile.display())).unwrap();
+        let mut client = Client::with_config(load_config_from_path(&config_path).unwrap()).unwrap();
+        let busy = temp_dir.path().join("busy");
+        let quiet = temp_dir.path().join("quiet");
+        for dir in [&busy, &quiet, &busy] {
+            fs::create_dir_all(dir).unwrap();
+            client.insert(dir.as_os_str()).unwrap();
+        }
+
+        let cli = Cli::parse_from(["zcd", "list", "--limit", "1", "--watch", "--interval", "5"]);
+        let Commands::List(args) = &cli.command else {
+            panic!("expected the list command");
+        };
+        assert_eq!(args.interval, 5);
+        let frame = format::redraw(&list_lines(&client, args).unwrap());
+        assert_eq!(frame, format!("\x1b[2J\x1b[H{}\n", busy.display()));
+    }
+
+    #[test]
+    fn watch_interval_must_be_at_least_a_second() {
+        for interval in ["0", "-1", "soon"] {
+            let parsed = Cli::try_parse_from(["zcd", "list", "--watch", "--interval", interval]);
+            assert!(parsed.is_err(), "{interval}");
+        }
+        let cli = Cli::try_parse_from(["zcd", "list", "--watch", "--interval", "1"]).unwrap();
+        let Commands::List(args) = &cli.command else {
+            panic!("expected the list command");
+        };
+        assert_eq!(args.interval, 1);
+    }
+
+    #[test]
+    fn query_jumps_where_which_resolves() {
+        let temp_dir = tempdir().unwrap();
+        let datafile = temp_dir.path().join("zcddata");
+        let config_path = temp_dir.path().join("config");
+        fs::write(&config_path, format!("datafile={}", datafile.display())).unwrap();
+        let mut client = Client::with_config(load_config_from_path(&config_path).unwrap()).unwrap();
+        let [alpha, beta] = ["proj-alpha", "proj-beta"].map(|name| temp_dir.path().join(name));
+        for dir in [&alpha, &beta, &beta] {
+            fs::create_dir_all(dir).unwrap();
+            client.insert(dir.as_os_str()).unwrap();
+        }
+
+        for pattern in ["proj", "alpha", "nothing-like-it"] {
+            let cli = Cli::parse_from(["zcd", "query", pattern]);
+            let Commands::Query(args) = &cli.command else {
+                panic!("expected the query command");
+            };
+            let jumped = query_matches(&client, args, false).unwrap();
+            assert!(jumped.len() <= 1);
+            let jumped = jumped.first().map(|scored| scored.dir.path.to_string());
+
+            let cli = Cli::parse_from(["zcd", "which", pattern]);
+            let Commands::Which { pattern } = &cli.command else {
+                panic!("expected the which command");
+            };
+            assert_eq!(jumped, client.resolve(pattern).unwrap(), "{pattern}");
+        }
+        let cli = Cli::parse_from(["zcd", "query", "proj"]);
+        let Commands::Query(args) = &cli.command else {
+            panic!("expected the query command");
+        };
+        let best = &query_matches(&client, args, false).unwrap()[0];
+        assert_eq!(Path::new(best.dir.path.as_ref()), beta);
+    }
+
+    #[test]
+    fn mapped_paths_keep_their_visits() {
+        let temp_dir = tempdir().unwrap();
+        let datafile = temp_dir.path().join("zcddata");
+        let config_path = temp_dir.path().join("config");
+        let project = temp_dir.path().join("workspace/project");
+        fs::create_dir_all(&project).unwrap();
+        fs::write(
+            &config_path,
+            format!(
+                "datafile={}\npath_map=[{}=>/home/me/src]",
+                datafile.display(),
+                temp_dir.path().join("workspace").display()
+            ),
+        )
+        .unwrap();
+        // Written before the mapping was configured, so stored unmapped.
+        fs::write(&datafile, format!("{}|3.0|1600000000\n", project.display())).unwrap();
+        let client = Client::with_config(load_config_from_path(&config_path).unwrap()).unwrap();
+
+        let cli = Cli::parse_from(["zcd", "query", "project"]);
+        let Commands::Query(args) = &cli.command else {
+            panic!("expected the query command");
+        };
+        let matches = query_matches(&client, args, false).unwrap();
+        assert_eq!(matches[0].dir.path, "/home/me/src/project");
+        assert_eq!(matches[0].visits, 3.0);
+
+        let cli = Cli::parse_from(["zcd", "list", "--porcelain"]);
+        let Commands::List(args) = &cli.command else {
+            panic!("expected the list command");
+        };
+        let line = &list_lines(&client, args).unwrap()[0];
+        let fields: Vec<&str> = line.split('\t').collect();
+        assert_eq!((fields[0], fields[2]), ("/home/me/src/project", "3.0"));
+    }
+
+    #[test]
+    fn which_leaves_the_datafile_untouched() {
+        let temp_dir = tempdir().unwrap();
+        let datafile = temp_dir.path().join("zcddata");
+        let config_path = temp_dir.path().join("config");
+        let project = temp_dir.path().join("project");
+        fs::create_dir(&project).unwrap();
+        fs::write(&config_path, format!("datafile={}", datafile.display())).unwrap();
+        fs::write(&datafile, format!("{}|3.0|1600000000\n", project.display())).unwrap();
+        let before = fs::read(&datafile).unwrap();
+        let modified = fs::metadata(&datafile).unwrap().modified().unwrap();
+
+        let cli = Cli::parse_from(["zcd", "which", "proj"]);
+        let Commands::Which { pattern } = &cli.command else {
+            panic!("expected the which command");
+        };
+        let client = Client::with_config(load_config_from_path(&config_path).unwrap()).unwrap();
+        assert_eq!(which(&client, pattern).unwrap(), project.to_str().unwrap());
+        assert!(which(&client, "nothing-like-it").is_err());
+        drop(client);
+
+        assert_eq!(fs::read(&datafile).unwrap(), before);
+        assert_eq!(
+            fs::metadata(&datafile).unwrap().modified().unwrap(),
+            modified
+        );
+    }
+
+    #[test]
+    fn bump_with_offset_records_the_best_match() {
+        let temp_dir = tempdir().unwrap();
+        let datafile = temp_dir.path().join("zcddata");
+        let config_path = temp_dir.path().join("config");
+        fs::write(&config_path, format!("datafile={}", datafile.display())).unwrap();
+        let mut client = Client::with_config(load_config_from_path(&config_path).unwrap()).unwrap();
+        let [alpha, beta] = ["proj-alpha", "proj-beta"].map(|name| temp_dir.path().join(name));
+        for dir in [&alpha, &alpha, &beta] {
+            fs::create_dir_all(dir).unwrap();
+            client.insert(dir.as_os_str()).unwrap();
+        }
+
+        let cli = Cli::parse_from(["zcd", "query", "proj", "--offset", "1", "--bump"]);
+        let Commands::Query(args) = &cli.command else {
+            panic!("expected the query command");
+        };
+        let shown: Vec<String> = query_matches(&client, args, false)
+            .unwrap()
+            .iter()
+            .map(|scored| scored.dir.path.to_string())
+            .collect();
+        assert_eq!(shown, [beta.to_str().unwrap()]);
+
+        let terms: Vec<&str> = args.entry.iter().map(String::as_str).collect();
+        let opts = query_options(&client, args);
+        assert!(client.bump(&terms, &opts).unwrap());
+        assert_eq!(client.visits(alpha.to_str().unwrap()), 3.0);
+        assert_eq!(client.visits(beta.to_str().unwrap()), 1.0);
+    }
+
+    #[test]
+    fn query_applies_the_tie_policy() {
+        let temp_dir = tempdir().unwrap();
+        let datafile = temp_dir.path().join("zcddata");
+        let config_path = temp_dir.path().join("config");
+        let long = temp_dir.path().join("deeper/nested/proj");
+        let short = temp_dir.path().join("x/proj");
+        for dir in [&long, &short] {
+            fs::create_dir_all(dir).unwrap();
+        }
+        // Same visit count and recency bucket give equal frecency, and with
+        // no leading gap penalty both paths score the same.
+        let now = now();
+        fs::write(
+            &datafile,
+            format!(
+                "{}|1.0|{}\n{}|1.0|{}\n",
+                long.display(),
+                now - 60,
+                short.display(),
+                now
+            ),
+        )
+        .unwrap();
+        let query = |policy: &str| {
+            fs::write(
+                &config_path,
+                format!(
+                    "datafile={}\nstable_order=true\nmax_leading_gap=0\ntie_policy={}",
+                    datafile.display(),
+                    policy
+                ),
+            )
+            .unwrap();
+            let client = Client::with_config(load_config_from_path(&config_path).unwrap()).unwrap();
+            let cli = Cli::parse_from(["zcd", "query", "proj"]);
+            let Commands::Query(args) = &cli.command else {
+                panic!("expected the query command");
+            };
+            query_matches(&client, args, false).map(|dirs| PathBuf::from(dirs[0].dir.path.as_ref()))
+        };
+
+        assert_eq!(query("first").unwrap(), long);
+        assert_eq!(query("recent").unwrap(), short);
+        assert_eq!(query("shortest").unwrap(), short);
+        let err = query("error").unwrap_err().to_string();
+        assert!(err.contains("2 entries tie for proj"), "got: {err}");
+    }
+
+    #[test]
+    fn reverse_lists_lowest_rank_first_also_when_grouped() {
+        let temp_dir = tempdir().unwrap();
+        let datafile = temp_dir.path().join("zcddata");
+        let config_path = temp_dir.path().join("config");
+        fs::write(&config_path, format!("datafile={}", datafile.display())).unwrap();
+        let mut client = Client::with_config(load_config_from_path(&config_path).unwrap()).unwrap();
+        let [high, mid, low] =
+            ["work/api", "work/web", "play"].map(|sub| temp_dir.path().join(sub));
+        for (dir, visits) in [(&high, 4), (&mid, 2), (&low, 1)] {
+            fs::create_dir_all(dir).unwrap();
+            for _ in 0..visits {
+                client.insert(dir.as_os_str()).unwrap();
+            }
+        }
+        let lines = |argv: &[&str]| {
+            let cli = Cli::parse_from(["zcd", "list"].iter().chain(argv));
+            let Commands::List(args) = &cli.command else {
+                panic!("expected the list command");
+            };
+            list_lines(&client, args).unwrap()
+        };
+
+        let shown = |dirs: &[&PathBuf]| -> Vec<String> {
+            dirs.iter().map(|dir| dir.display().to_string()).collect()
+        };
+        assert_eq!(lines(&[]), shown(&[&high, &mid, &low]));
+        assert_eq!(lines(&["--reverse"]), shown(&[&low, &mid, &high]));
+
+        let depth = temp_dir.path().components().count().to_string();
+        let groups = lines(&["--reverse", "--group-by", &depth]);
+        let prefix = |line: &str| line.rsplit(' ').next().unwrap().to_string();
+        assert_eq!(groups.len(), 2);
+        assert_eq!(
+            prefix(&groups[0]),
+            temp_dir.path().join("play").display().to_string()
+        );
+        assert_eq!(
+            prefix(&groups[1]),
+            temp_dir.path().join("work").display().to_string()
+        );
+    }
+
+    #[test]
+    fn no_existence_check_returns_a_missing_match() {
+        let temp_dir = tempdir().unwrap();
+        let datafile = temp_dir.path().join("zcddata");
+        let config_path = temp_dir.path().join("config");
+        fs::write(&config_path, format!("datafile={}", datafile.display())).unwrap();
+        let gone = temp_dir.path().join("gone-proj");
+        fs::write(&datafile, format!("{}|3.0|{}\n", gone.display(), now())).unwrap();
+        let client = Client::with_config(load_config_from_path(&config_path).unwrap()).unwrap();
+        let matches = |argv: &[&str]| {
+            let cli = Cli::parse_from(["zcd", "query"].iter().chain(argv));
+            let Commands::Query(args) = &cli.command else {
+                panic!("expected the query command");
+            };
+            query_matches(&client, args, false)
+                .unwrap()
+                .into_iter()
+                .map(|scored| PathBuf::from(scored.dir.path.as_ref()))
+                .collect::<Vec<_>>()
+        };
+
+        assert!(matches(&["gone"]).is_empty());
+        assert_eq!(matches(&["--no-existence-check", "gone"]), [gone]);
+    }
+
+    #[test]
+    fn ndjson_lines_parse_on_their_own() {
+        let temp_dir = tempdir().unwrap();
+        let datafile = temp_dir.path().join("zcddata");
+        let config_path = temp_dir.path().join("config");
+        fs::write(&config_path, format!("datafile={}", datafile.display())).unwrap();
+        let mut client = Client::with_config(load_config_from_path(&config_path).unwrap()).unwrap();
+        let dirs = ["plain", "with \"quotes\"", "back\\slash", "tab\there"]
+            .map(|name| temp_dir.path().join(name));
+        for dir in &dirs {
+            fs::create_dir_all(dir).unwrap();
+            client.insert(dir.as_os_str()).unwrap();
+        }
+
+        let cli = Cli::parse_from(["zcd", "list", "--ndjson"]);
+        let Commands::List(args) = &cli.command else {
+            panic!("expected the list command");
+        };
+        let mut out = Vec::new();
+        assert_eq!(write_ndjson(&client, args, &mut out).unwrap(), dirs.len());
+        let text = String::from_utf8(out).unwrap();
+        let mut paths: Vec<String> = text
+            .lines()
+            .map(|line| {
+                let value: serde_json::Value = serde_json::from_str(line).unwrap();
+                assert_eq!(value["visits"], 1.0);
+                assert!(value["rank"].as_f64().unwrap() > 0.0);
+                assert!(value["last_accessed"].as_u64().is_some());
+                value["path"].as_str().unwrap().to_string()
+            })
+            .collect();
+        paths.sort();
+        let mut expected: Vec<String> = dirs
+            .iter()
+            .map(|dir| dir.to_string_lossy().into_owned())
+            .collect();
+        expected.sort();
+        assert_eq!(paths, expected);
+        assert!(text.ends_with('\n'));
+
+        let cli = Cli::parse_from(["zcd", "list", "--ndjson", "--limit", "2"]);
+        let Commands::List(args) = &cli.command else {
+            panic!("expected the list command");
+        };
+        assert_eq!(write_ndjson(&client, args, io::sink()).unwrap(), 2);
+    }
+
+    #[test]
+    fn examples_cover_the_insert_hook_and_query() {
+        for shell in ["zsh", "bash", "fish"] {
+            assert!(EXAMPLES.contains(&format!("# {}", shell)), "{shell}");
+        }
+        assert!(EXAMPLES.contains("zcd insert -- \"$PWD\""));
+        assert!(EXAMPLES.contains("zcd query -- \"$@\""));
+    }
+
+    #[test]
+    fn clear_confirmation_needs_yes_or_an_interactive_y() {
+        let mut prompt = Vec::new();
+        assert!(confirm_removal(3, true, false, &b""[..], &mut prompt).unwrap());
+        assert!(prompt.is_empty(), "--yes never prompts");
+
+        let err = confirm_removal(3, false, false, &b"y\n"[..], &mut prompt).unwrap_err();
+        assert!(err.to_string().contains("--yes"), "got: {err}");
+        assert!(prompt.is_empty(), "no prompt outside a terminal");
+
+        assert!(confirm_removal(3, false, true, &b"Y\n"[..], &mut prompt).unwrap());
+        assert_eq!(
+            String::from_utf8(prompt).unwrap(),
+            "This will delete 3 entries. Continue? (y/N): "
+        );
+        assert!(!confirm_removal(3, false, true, &b"\n"[..], Vec::new()).unwrap());
+    }
+
+    #[test]
+    fn a_database_match_wins_over_the_literal_fallback() {
+        let temp_dir = tempdir().unwrap();
+        let datafile = temp_dir.path().join("zcddata");
+        let config_path = temp_dir.path().join("config");
+        fs::write(&config_path, format!("datafile={}", datafile.display())).unwrap();
+        let mut client = Client::with_config(load_config_from_path(&config_path).unwrap()).unwrap();
+        let literal = temp_dir.path().join("work");
+        let tracked = temp_dir.path().join("work-project");
+        for dir in [&literal, &tracked] {
+            fs::create_dir(dir).unwrap();
+        }
+        client.insert(tracked.as_os_str()).unwrap();
+
+        let pattern = literal.to_str().unwrap();
+        let cli = Cli::parse_from(["zcd", "query", "--fallback-literal", pattern]);
+        let Commands::Query(args) = &cli.command else {
+            panic!("expected the query command");
+        };
+        let matches = query_matches(&client, args, false).unwrap();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(Path::new(matches[0].dir.path.as_ref()), tracked);
+
+        client.delete(tracked.to_str().unwrap()).unwrap();
+        assert!(query_matches(&client, args, false).unwrap().is_empty());
+        assert_eq!(
+            unmatched_target(&mut client, args).unwrap().as_deref(),
+            Some(pattern),
+            "without a match the existing directory is the target"
+        );
+    }
+
+    #[test]
+    fn literal_fallback_needs_an_existing_directory() {
+        let temp_dir = tempdir().unwrap();
+        let dir = vec![temp_dir.path().to_string_lossy().into_owned()];
+        let missing = vec![temp_dir
+            .path()
+            .join("missing")
+            .to_string_lossy()
+            .into_owned()];
+
+        assert_eq!(literal_fallback(&dir, true), Some(dir[0].as_str()));
+        assert_eq!(literal_fallback(&missing, true), None);
+        assert_eq!(literal_fallback(&dir, false), None);
+        assert_eq!(
+            literal_fallback(&[dir[0].clone(), dir[0].clone()], true),
+            None
+        );
+    }
+}
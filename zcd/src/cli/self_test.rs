@@ -0,0 +1,68 @@
+//! `zcd self-test`: an insert/query/delete round trip against a throwaway
+//! datafile, for checking a packaged binary without touching real data.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+
+use crate::config::load_config_from_path;
+use crate::db::dir::OpsDelegate;
+use crate::db::Database;
+
+/// Run the round trip in a fresh, uniquely named directory under the
+/// system temp dir, which is removed afterwards whatever the outcome.
+pub fn run() -> Result<()> {
+    let root = tempfile::Builder::new()
+        .prefix("zcd-self-test-")
+        .tempdir()
+        .context("cannot create a temporary directory")?;
+    run_in(root.path())
+}
+
+fn run_in(root: &Path) -> Result<()> {
+    let config = root.join("config");
+    let datafile = root.join("data");
+    fs::write(&config, format!("datafile={}\n", datafile.display()))
+        .context("cannot write the test config")?;
+    let target: PathBuf = root.join("self-test-target");
+    fs::create_dir(&target).context("cannot create the test directory")?;
+    let target = target.to_string_lossy().into_owned();
+
+    let mut db = open(&config, &datafile)?;
+    db.insert_or_update(target.clone().into());
+    db.save()?;
+
+    let db = open(&config, &datafile)?;
+    match db.resolve("self-test-target")? {
+        Some(found) if found == target => {}
+        Some(other) => bail!("query returned {} instead of {}", other, target),
+        None => bail!("query did not find {}", target),
+    }
+
+    let mut db = db;
+    db.delete(&target);
+    db.save()?;
+    if open(&config, &datafile)?.get(&target).is_some() {
+        bail!("{} is still stored after delete", target);
+    }
+    Ok(())
+}
+
+/// Open the test database, pinned to `datafile` even when
+/// `$ZCD_DATA_FILE` points somewhere else.
+fn open(config: &Path, datafile: &Path) -> Result<Database<'static>> {
+    let mut config = load_config_from_path(config)?;
+    config.datafile = datafile.to_path_buf();
+    Database::with_config(config)
+}
+
+#[cfg(test)]
+mod test_self_test {
+    use super::*;
+
+    #[test]
+    fn self_test_passes() {
+        run().unwrap();
+    }
+}
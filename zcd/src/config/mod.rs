@@ -1,5 +1,9 @@
+use std::borrow::Cow;
 use std::char::ParseCharError;
+use std::collections::HashMap;
 use std::env;
+use std::ffi::OsString;
+use std::fmt;
 use std::fs;
 use std::fs::File;
 use std::io;
@@ -9,12 +13,94 @@ use std::str::FromStr;
 
 use anyhow::{anyhow, Context, Result};
 
+use crate::db::dir::is_under;
+use crate::db::{DataFormat, LineOrder};
+use crate::fuzzy::{SCORE_GAP_LEADING, SCORE_MATCH_SLASH};
+
 pub fn home_dir() -> Option<PathBuf> {
     env::var_os("HOME")
         .and_then(|h| if h.is_empty() { None } else { Some(h) })
         .map(PathBuf::from)
 }
 
+/// What `exclude_dirs` does to paths inside them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExcludeMode {
+    /// Never insert or update them.
+    #[default]
+    Skip,
+    /// Keep tracking them, but leave them out of query and list results.
+    Hide,
+}
+
+impl FromStr for ExcludeMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "skip" => Ok(ExcludeMode::Skip),
+            "hide" => Ok(ExcludeMode::Hide),
+            _ => Err(anyhow!(
+                "unknown exclude_mode {:?}; expected skip or hide",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ExcludeMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ExcludeMode::Skip => "skip",
+            ExcludeMode::Hide => "hide",
+        })
+    }
+}
+
+/// How [`crate::db::Database::resolve_terms`], behind both `query` and
+/// `which`, picks among matches that tie on both match quality and
+/// frecency.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TiePolicy {
+    /// Keep query order: by path with `stable_order`, arbitrary otherwise.
+    #[default]
+    First,
+    /// The most recently visited.
+    Recent,
+    /// The shortest path.
+    Shortest,
+    /// Fail and ask for a more specific pattern.
+    Error,
+}
+
+impl FromStr for TiePolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "first" => Ok(TiePolicy::First),
+            "recent" => Ok(TiePolicy::Recent),
+            "shortest" => Ok(TiePolicy::Shortest),
+            "error" => Ok(TiePolicy::Error),
+            _ => Err(anyhow!(
+                "unknown tie_policy {:?}; expected first, recent, shortest or error",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for TiePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TiePolicy::First => "first",
+            TiePolicy::Recent => "recent",
+            TiePolicy::Shortest => "shortest",
+            TiePolicy::Error => "error",
+        })
+    }
+}
+
 #[derive(Debug)]
 pub struct Config {
     /// Rank aging threshold: when the sum of all ranks exceeds this value,
@@ -23,18 +109,184 @@ pub struct Config {
     /// debug mode
     #[allow(dead_code)]
     pub debug: bool,
-    /// paths to exclude for z
-    #[allow(dead_code)]
+    /// paths to exclude for z, tilde/env expanded and absolute
     pub exclude_dirs: Vec<String>,
-    /// datafile path
-    pub datafile: String,
+    /// whether excluded paths are skipped on insert or hidden from results
+    pub exclude_mode: ExcludeMode,
+    /// datafile path, tilde/env expanded and absolute
+    pub datafile: PathBuf,
+    /// line format of the datafile itself
+    pub datafile_format: DataFormat,
+    /// line order of a z-format datafile
+    pub datafile_sort: LineOrder,
+    /// fuzzy bonus for matching right after a `/`
+    pub slash_bonus: f64,
+    /// fuzzy score per path character before the first match (negative)
+    pub gap_leading: f64,
+    /// cap on the total leading gap penalty
+    pub max_leading_gap: f64,
+    /// longest path (in bytes) accepted by insert
+    pub max_path_len: usize,
+    /// break frecency ties by path so pickers list entries in a fixed order
+    pub stable_order: bool,
+    /// how resolve picks among matches tied for best
+    pub tie_policy: TiePolicy,
+    /// ignore a repeat insert of the last inserted path within this window
+    pub insert_debounce_ms: u64,
+    /// count subcommand runs in a local sidecar file
+    pub usage_stats: bool,
+    /// extra weight for query terms matching a path's last component
+    pub basename_boost: f64,
+    /// score subtracted per path component beyond the first few
+    pub depth_penalty: f64,
+    /// fraction of every rank lost per day the datafile went unsaved
+    pub idle_decay_rate: f64,
+    /// never record the home directory itself
+    pub exclude_home: bool,
+    /// refuse to insert paths that exist but are not directories
+    pub require_dir: bool,
+    /// remember device and inode numbers so history can follow renames
+    pub track_inode: bool,
+    /// treat paths differing only in case as one directory
+    pub fold_case: bool,
+    /// a query for a tracked path returns that path without fuzzy scoring
+    pub exact_path: bool,
+    /// frecency multipliers for directories under a prefix, from
+    /// `weight:<prefix>=<multiplier>` lines; prefixes are expanded
+    pub weights: Vec<(PathBuf, f64)>,
+    /// `(from, to)` prefixes rewritten on insert and display, from
+    /// `path_map=[from=>to,...]`; both sides are expanded
+    pub path_map: Vec<(String, String)>,
+    /// where each key's value came from; absent keys are defaults
+    sources: HashMap<&'static str, ConfigSource>,
+}
+
+/// Where a resolved config value came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigSource {
+    Default,
+    File,
+    Env,
+}
+
+impl fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ConfigSource::Default => "default",
+            ConfigSource::File => "file",
+            ConfigSource::Env => "env",
+        })
+    }
+}
+
+impl Config {
+    pub fn source(&self, key: &str) -> ConfigSource {
+        self.sources
+            .get(key)
+            .copied()
+            .unwrap_or(ConfigSource::Default)
+    }
+
+    /// Rewrite `path` through the longest `path_map` prefix containing it,
+    /// matching whole components only.
+    pub fn map_path<'a>(&self, path: &'a str) -> Cow<'a, str> {
+        self.path_map
+            .iter()
+            .filter(|(from, _)| is_under(path, from))
+            .max_by_key(|(from, _)| from.len())
+            .map_or(Cow::Borrowed(path), |(from, to)| {
+                Cow::Owned(format!("{}{}", to, &path[from.len()..]))
+            })
+    }
+
+    /// Whether `path` is inside one of `exclude_dirs`.
+    pub fn is_excluded(&self, path: &str) -> bool {
+        self.exclude_dirs.iter().any(|dir| is_under(path, dir))
+    }
+
+    /// Every key with its resolved value, formatted as it would be written
+    /// in a config file, and where that value came from. Each weight is its
+    /// own `weight:<prefix>` key, so `key=value` lines read back as this
+    /// same config.
+    pub fn entries(&self) -> Vec<(String, String, ConfigSource)> {
+        let values = [
+            ("max_age", self.max_age.to_string()),
+            ("datafile", self.datafile.display().to_string()),
+            ("datafile_format", self.datafile_format.to_string()),
+            ("datafile_sort", self.datafile_sort.to_string()),
+            ("exclude_dirs", format!("[{}]", self.exclude_dirs.join(","))),
+            ("exclude_mode", self.exclude_mode.to_string()),
+            ("debug", self.debug.to_string()),
+            ("slash_bonus", self.slash_bonus.to_string()),
+            ("gap_leading", self.gap_leading.to_string()),
+            ("max_leading_gap", self.max_leading_gap.to_string()),
+            ("max_path_len", self.max_path_len.to_string()),
+            ("stable_order", self.stable_order.to_string()),
+            ("tie_policy", self.tie_policy.to_string()),
+            ("insert_debounce_ms", self.insert_debounce_ms.to_string()),
+            ("usage_stats", self.usage_stats.to_string()),
+            ("basename_boost", self.basename_boost.to_string()),
+            ("depth_penalty", self.depth_penalty.to_string()),
+            ("idle_decay_rate", self.idle_decay_rate.to_string()),
+            ("exclude_home", self.exclude_home.to_string()),
+            ("require_dir", self.require_dir.to_string()),
+            ("track_inode", self.track_inode.to_string()),
+            ("fold_case", self.fold_case.to_string()),
+            ("exact_path", self.exact_path.to_string()),
+            (
+                "path_map",
+                format!(
+                    "[{}]",
+                    self.path_map
+                        .iter()
+                        .map(|(from, to)| format!("{}=>{}", from, to))
+                        .collect::<Vec<_>>()
+                        .join(",")
+                ),
+            ),
+        ];
+        let weights = self.weights.iter().map(|(prefix, weight)| {
+            (
+                format!("weight:{}", prefix.display()),
+                weight.to_string(),
+                self.source("weights"),
+            )
+        });
+        values
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value, self.source(key)))
+            .chain(weights)
+            .collect()
+    }
 }
 
 pub struct ConfigBuilder {
     max_age: u64,
     debug: bool,
     exclude_dirs: Vec<String>,
-    datafile: String,
+    exclude_mode: ExcludeMode,
+    datafile: PathBuf,
+    datafile_format: DataFormat,
+    datafile_sort: LineOrder,
+    slash_bonus: f64,
+    gap_leading: f64,
+    max_leading_gap: f64,
+    max_path_len: usize,
+    stable_order: bool,
+    tie_policy: TiePolicy,
+    insert_debounce_ms: u64,
+    usage_stats: bool,
+    basename_boost: f64,
+    depth_penalty: f64,
+    idle_decay_rate: f64,
+    exclude_home: bool,
+    require_dir: bool,
+    track_inode: bool,
+    fold_case: bool,
+    exact_path: bool,
+    weights: Vec<(PathBuf, f64)>,
+    path_map: Vec<(String, String)>,
+    sources: HashMap<&'static str, ConfigSource>,
 }
 
 impl ConfigBuilder {
@@ -45,7 +297,29 @@ impl ConfigBuilder {
             max_age: 30000, // 5 * 60 * 1000
             debug: false,
             exclude_dirs: vec![],
-            datafile: datafile.display().to_string(),
+            exclude_mode: ExcludeMode::default(),
+            datafile,
+            datafile_format: DataFormat::default(),
+            datafile_sort: LineOrder::default(),
+            slash_bonus: SCORE_MATCH_SLASH,
+            gap_leading: SCORE_GAP_LEADING,
+            max_leading_gap: f64::INFINITY,
+            max_path_len: 4096,
+            stable_order: false,
+            tie_policy: TiePolicy::default(),
+            insert_debounce_ms: 0,
+            usage_stats: false,
+            basename_boost: 0.0,
+            depth_penalty: 0.0,
+            idle_decay_rate: 0.0,
+            exclude_home: false,
+            require_dir: false,
+            track_inode: false,
+            fold_case: false,
+            exact_path: true,
+            weights: vec![],
+            path_map: vec![],
+            sources: HashMap::new(),
         }
     }
     pub fn max_age(&mut self, max_age: u64) -> &mut Self {
@@ -63,19 +337,179 @@ impl ConfigBuilder {
         self
     }
 
-    pub fn datafile(&mut self, path: String) -> &mut Self {
+    pub fn exclude_mode(&mut self, mode: ExcludeMode) -> &mut Self {
+        self.exclude_mode = mode;
+        self
+    }
+
+    pub fn datafile(&mut self, path: PathBuf) -> &mut Self {
         self.datafile = path;
         self
     }
 
+    pub fn datafile_format(&mut self, format: DataFormat) -> &mut Self {
+        self.datafile_format = format;
+        self
+    }
+
+    pub fn datafile_sort(&mut self, order: LineOrder) -> &mut Self {
+        self.datafile_sort = order;
+        self
+    }
+
+    pub fn slash_bonus(&mut self, bonus: f64) -> &mut Self {
+        self.slash_bonus = bonus;
+        self
+    }
+
+    pub fn gap_leading(&mut self, gap: f64) -> &mut Self {
+        self.gap_leading = gap;
+        self
+    }
+
+    pub fn max_leading_gap(&mut self, cap: f64) -> &mut Self {
+        self.max_leading_gap = cap;
+        self
+    }
+
+    pub fn max_path_len(&mut self, len: usize) -> &mut Self {
+        self.max_path_len = len;
+        self
+    }
+
+    pub fn stable_order(&mut self, stable: bool) -> &mut Self {
+        self.stable_order = stable;
+        self
+    }
+
+    pub fn tie_policy(&mut self, policy: TiePolicy) -> &mut Self {
+        self.tie_policy = policy;
+        self
+    }
+
+    pub fn insert_debounce_ms(&mut self, ms: u64) -> &mut Self {
+        self.insert_debounce_ms = ms;
+        self
+    }
+
+    pub fn usage_stats(&mut self, enabled: bool) -> &mut Self {
+        self.usage_stats = enabled;
+        self
+    }
+
+    pub fn basename_boost(&mut self, boost: f64) -> &mut Self {
+        self.basename_boost = boost;
+        self
+    }
+
+    pub fn depth_penalty(&mut self, penalty: f64) -> &mut Self {
+        self.depth_penalty = penalty;
+        self
+    }
+
+    pub fn idle_decay_rate(&mut self, rate: f64) -> &mut Self {
+        self.idle_decay_rate = rate;
+        self
+    }
+
+    /// Set the multiplier for `prefix`, replacing an earlier one.
+    pub fn weight(&mut self, prefix: PathBuf, weight: f64) -> &mut Self {
+        self.weights.retain(|(p, _)| *p != prefix);
+        self.weights.push((prefix, weight));
+        self
+    }
+
+    pub fn exclude_home(&mut self, exclude: bool) -> &mut Self {
+        self.exclude_home = exclude;
+        self
+    }
+
+    pub fn require_dir(&mut self, require: bool) -> &mut Self {
+        self.require_dir = require;
+        self
+    }
+
+    pub fn track_inode(&mut self, track: bool) -> &mut Self {
+        self.track_inode = track;
+        self
+    }
+
+    pub fn fold_case(&mut self, fold: bool) -> &mut Self {
+        self.fold_case = fold;
+        self
+    }
+
+    pub fn exact_path(&mut self, exact: bool) -> &mut Self {
+        self.exact_path = exact;
+        self
+    }
+
+    pub fn path_map(&mut self, map: Vec<(String, String)>) -> &mut Self {
+        self.path_map = map;
+        self
+    }
+
+    /// Record that `key` was set from `source` rather than defaulted.
+    fn source(&mut self, key: &'static str, source: ConfigSource) -> &mut Self {
+        self.sources.insert(key, source);
+        self
+    }
+
     pub fn build(&mut self) -> Config {
         Config {
             max_age: self.max_age,
             debug: self.debug,
             exclude_dirs: self.exclude_dirs.clone(),
+            exclude_mode: self.exclude_mode,
             datafile: self.datafile.clone(),
+            datafile_format: self.datafile_format,
+            datafile_sort: self.datafile_sort,
+            slash_bonus: self.slash_bonus,
+            gap_leading: self.gap_leading,
+            max_leading_gap: self.max_leading_gap,
+            max_path_len: self.max_path_len,
+            stable_order: self.stable_order,
+            tie_policy: self.tie_policy,
+            insert_debounce_ms: self.insert_debounce_ms,
+            usage_stats: self.usage_stats,
+            basename_boost: self.basename_boost,
+            depth_penalty: self.depth_penalty,
+            idle_decay_rate: self.idle_decay_rate,
+            exclude_home: self.exclude_home,
+            require_dir: self.require_dir,
+            track_inode: self.track_inode,
+            fold_case: self.fold_case,
+            exact_path: self.exact_path,
+            weights: self.weights.clone(),
+            path_map: self.path_map.clone(),
+            sources: self.sources.clone(),
+        }
+    }
+}
+
+/// Expand a leading `~` and any `$VAR`/`${VAR}` path component, then make
+/// the result absolute against the current directory.
+pub fn expand_path<P: AsRef<Path>>(p: P) -> Result<PathBuf> {
+    let path = p.as_ref();
+    let mut expanded = PathBuf::new();
+    for (i, component) in path.iter().enumerate() {
+        let part = component.to_string_lossy();
+        if i == 0 && part == "~" {
+            expanded.push(home_dir().context("cannot resolve home directory")?);
+        } else if let Some(name) = part
+            .strip_prefix("${")
+            .and_then(|rest| rest.strip_suffix('}'))
+            .or_else(|| part.strip_prefix('$'))
+        {
+            let value = env::var_os(name)
+                .with_context(|| format!("environment variable {} is not set", name))?;
+            expanded.push(value);
+        } else {
+            expanded.push(component);
         }
     }
+    std::path::absolute(&expanded)
+        .with_context(|| format!("cannot make {} absolute", expanded.display()))
 }
 
 pub fn config_dir() -> Option<PathBuf> {
@@ -86,10 +520,49 @@ pub fn config_dir() -> Option<PathBuf> {
     dir.map(|d| d.join("zcd"))
 }
 
+/// Directory for runtime state that is neither config nor history, such
+/// as usage counters: `$XDG_STATE_HOME/zcd`, else `~/.local/state/zcd`.
+/// Callers create it when they first write there.
+pub fn state_dir() -> Option<PathBuf> {
+    state_dir_from(env::var_os("XDG_STATE_HOME"), home_dir())
+}
+
+fn state_dir_from(xdg_state_home: Option<OsString>, home: Option<PathBuf>) -> Option<PathBuf> {
+    let dir = xdg_state_home
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .or_else(|| home.map(|d| d.join(".local/state")));
+    dir.map(|d| d.join("zcd"))
+}
+
 pub fn config_exists() -> bool {
     config_file().unwrap().exists()
 }
 
+/// Config files to load, in override order. `$ZCD_CONFIG_FILE` may hold a
+/// colon-separated list (base first, overlays after). Every listed file is
+/// kept, so one that does not exist fails the load instead of silently
+/// dropping its keys. A single path that is not a file falls back to the
+/// default config, as before lists were supported.
+pub fn config_files() -> Vec<PathBuf> {
+    config_files_from(env::var_os("ZCD_CONFIG_FILE"), config_file())
+}
+
+fn config_files_from(list: Option<OsString>, default: Option<PathBuf>) -> Vec<PathBuf> {
+    let files: Vec<PathBuf> = list
+        .map(|list| {
+            env::split_paths(&list)
+                .filter(|p| !p.as_os_str().is_empty())
+                .collect()
+        })
+        .unwrap_or_default();
+    match files.as_slice() {
+        [] => default.into_iter().collect(),
+        [single] if !single.is_file() => default.into_iter().collect(),
+        _ => files,
+    }
+}
+
 pub fn config_file() -> Option<PathBuf> {
     Some(
         env::var("ZCD_CONFIG_FILE")
@@ -100,15 +573,39 @@ pub fn config_file() -> Option<PathBuf> {
     )
 }
 
-pub fn generate_config_file() -> Result<()> {
+/// Written by `zcd config --generate`.
+const DEFAULT_CONFIG: &str = r#"# This is zcd's configuration file.
+# Rank aging threshold: when the sum of all ranks exceeds this value,
+# ranks are decayed (multiplied by 0.9) and negligible entries dropped.
+max_age=5000
+# Datafile (z-compatible plain text: path|rank|last_accessed)
+datafile=~/.zcddata
+# Exclude dirs
+# eg. exclude_dirs=~/tmp,
+exclude_dirs=[]
+"#;
+
+/// Write the default config file, asking before overwriting. With
+/// `dry_run` the file contents are printed instead and nothing is touched.
+pub fn generate_config_file(dry_run: bool) -> Result<()> {
     let config_file = config_file().context("cannot resolve config file path")?;
+    write_default_config(&config_file, dry_run, &mut io::stdout())
+}
+
+fn write_default_config<W: Write>(config_file: &Path, dry_run: bool, out: &mut W) -> Result<()> {
+    if dry_run {
+        out.write_all(DEFAULT_CONFIG.as_bytes())
+            .context("failed to print config")?;
+        return Ok(());
+    }
     if config_file.exists() {
-        println!(
+        writeln!(
+            out,
             "The zcd config file already exists at: {}",
             config_file.to_string_lossy()
-        );
-        print!("Overwrite? (y/N): ");
-        io::stdout().flush().context("failed to flush stdout")?;
+        )?;
+        write!(out, "Overwrite? (y/N): ")?;
+        out.flush().context("failed to flush stdout")?;
         let mut answer = String::new();
         io::stdin()
             .read_line(&mut answer)
@@ -121,22 +618,13 @@ pub fn generate_config_file() -> Result<()> {
             .with_context(|| format!("failed to create config dir {}", parent.display()))?;
     }
 
-    let default_config = r#"# This is zcd's configuration file.
-# Rank aging threshold: when the sum of all ranks exceeds this value,
-# ranks are decayed (multiplied by 0.9) and negligible entries dropped.
-max_age=5000
-# Datafile (z-compatible plain text: path|rank|last_accessed)
-datafile=~/.zcddata
-# Exclude dirs
-# eg. exclude_dirs=~/tmp,
-exclude_dirs=[]
-"#;
-    fs::write(&config_file, default_config)
+    fs::write(config_file, DEFAULT_CONFIG)
         .with_context(|| format!("failed to write config to {}", config_file.display()))?;
-    println!(
+    writeln!(
+        out,
         "Successfully wrote zcd config file to {}",
         config_file.to_string_lossy()
-    );
+    )?;
     Ok(())
 }
 
@@ -146,10 +634,35 @@ pub fn load_default_config() -> Result<Config> {
 }
 
 pub fn load_config_from_path<P: AsRef<Path>>(path: P) -> Result<Config> {
-    let path = path.as_ref();
+    load_config_from_paths(&[path])
+}
+
+/// Load several config files into one `Config`; keys in later files
+/// override the same keys in earlier ones. `$ZCD_DATA_FILE` overrides the
+/// datafile of the merged result.
+pub fn load_config_from_paths<P: AsRef<Path>>(paths: &[P]) -> Result<Config> {
+    let mut args = vec![];
+    for path in paths {
+        args.extend(read_config_file(path.as_ref())?);
+    }
+    let mut config = parse_config(args)?;
+    apply_datafile_override(&mut config, env::var_os("ZCD_DATA_FILE"))?;
+    Ok(config)
+}
+
+fn apply_datafile_override(config: &mut Config, datafile: Option<OsString>) -> Result<()> {
+    if let Some(datafile) = datafile.filter(|d| !d.is_empty()) {
+        config.datafile = expand_path(&datafile)
+            .with_context(|| format!("invalid ZCD_DATA_FILE: {}", datafile.to_string_lossy()))?;
+        config.sources.insert("datafile", ConfigSource::Env);
+    }
+    Ok(())
+}
+
+fn read_config_file(path: &Path) -> Result<Vec<ConfigLine>> {
     if path.exists() && path.is_file() {
         return match File::open(path) {
-            Ok(file) => read_config(file),
+            Ok(file) => read_config_lines(file, path),
             Err(err) => Err(anyhow!(format!("{}: {}", path.display(), err))),
         };
     }
@@ -163,11 +676,66 @@ pub fn load_config_from_path<P: AsRef<Path>>(path: P) -> Result<Config> {
 enum ConfigKeyWord {
     MaxAge,
     ExcludeDirs,
+    ExcludeMode,
     Datafile,
+    DatafileFormat,
+    DatafileSort,
     Debug,
+    SlashBonus,
+    GapLeading,
+    MaxLeadingGap,
+    MaxPathLen,
+    StableOrder,
+    TiePolicy,
+    InsertDebounceMs,
+    UsageStats,
+    BasenameBoost,
+    DepthPenalty,
+    IdleDecayRate,
+    ExcludeHome,
+    RequireDir,
+    TrackInode,
+    FoldCase,
+    ExactPath,
+    PathMap,
+    /// `weight:<prefix>`, carrying the prefix.
+    Weight(String),
     InvalidKeyword,
 }
 
+impl ConfigKeyWord {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ConfigKeyWord::MaxAge => "max_age",
+            ConfigKeyWord::ExcludeDirs => "exclude_dirs",
+            ConfigKeyWord::ExcludeMode => "exclude_mode",
+            ConfigKeyWord::Datafile => "datafile",
+            ConfigKeyWord::DatafileFormat => "datafile_format",
+            ConfigKeyWord::DatafileSort => "datafile_sort",
+            ConfigKeyWord::Debug => "debug",
+            ConfigKeyWord::SlashBonus => "slash_bonus",
+            ConfigKeyWord::GapLeading => "gap_leading",
+            ConfigKeyWord::MaxLeadingGap => "max_leading_gap",
+            ConfigKeyWord::MaxPathLen => "max_path_len",
+            ConfigKeyWord::StableOrder => "stable_order",
+            ConfigKeyWord::TiePolicy => "tie_policy",
+            ConfigKeyWord::InsertDebounceMs => "insert_debounce_ms",
+            ConfigKeyWord::UsageStats => "usage_stats",
+            ConfigKeyWord::BasenameBoost => "basename_boost",
+            ConfigKeyWord::DepthPenalty => "depth_penalty",
+            ConfigKeyWord::IdleDecayRate => "idle_decay_rate",
+            ConfigKeyWord::ExcludeHome => "exclude_home",
+            ConfigKeyWord::RequireDir => "require_dir",
+            ConfigKeyWord::TrackInode => "track_inode",
+            ConfigKeyWord::FoldCase => "fold_case",
+            ConfigKeyWord::ExactPath => "exact_path",
+            ConfigKeyWord::PathMap => "path_map",
+            ConfigKeyWord::Weight(_) => "weights",
+            ConfigKeyWord::InvalidKeyword => "",
+        }
+    }
+}
+
 impl FromStr for ConfigKeyWord {
     type Err = ParseCharError;
 
@@ -177,10 +745,52 @@ impl FromStr for ConfigKeyWord {
             ConfigKeyWord::MaxAge
         } else if key == "exclude_dirs" {
             ConfigKeyWord::ExcludeDirs
+        } else if key == "exclude_mode" {
+            ConfigKeyWord::ExcludeMode
         } else if key == "debug" {
             ConfigKeyWord::Debug
         } else if key == "datafile" {
             ConfigKeyWord::Datafile
+        } else if key == "datafile_format" {
+            ConfigKeyWord::DatafileFormat
+        } else if key == "datafile_sort" {
+            ConfigKeyWord::DatafileSort
+        } else if key == "slash_bonus" {
+            ConfigKeyWord::SlashBonus
+        } else if key == "gap_leading" {
+            ConfigKeyWord::GapLeading
+        } else if key == "max_leading_gap" {
+            ConfigKeyWord::MaxLeadingGap
+        } else if key == "max_path_len" {
+            ConfigKeyWord::MaxPathLen
+        } else if key == "stable_order" {
+            ConfigKeyWord::StableOrder
+        } else if key == "tie_policy" {
+            ConfigKeyWord::TiePolicy
+        } else if key == "insert_debounce_ms" {
+            ConfigKeyWord::InsertDebounceMs
+        } else if key == "usage_stats" {
+            ConfigKeyWord::UsageStats
+        } else if key == "basename_boost" {
+            ConfigKeyWord::BasenameBoost
+        } else if key == "depth_penalty" {
+            ConfigKeyWord::DepthPenalty
+        } else if key == "idle_decay_rate" {
+            ConfigKeyWord::IdleDecayRate
+        } else if key == "exclude_home" {
+            ConfigKeyWord::ExcludeHome
+        } else if key == "require_dir" {
+            ConfigKeyWord::RequireDir
+        } else if key == "track_inode" {
+            ConfigKeyWord::TrackInode
+        } else if key == "fold_case" {
+            ConfigKeyWord::FoldCase
+        } else if key == "exact_path" {
+            ConfigKeyWord::ExactPath
+        } else if key == "path_map" {
+            ConfigKeyWord::PathMap
+        } else if let Some(prefix) = key.strip_prefix("weight:") {
+            ConfigKeyWord::Weight(prefix.to_string())
         } else {
             ConfigKeyWord::InvalidKeyword
         };
@@ -188,15 +798,30 @@ impl FromStr for ConfigKeyWord {
     }
 }
 
-fn parse_config(args: Vec<String>) -> Result<Config> {
+/// One `key=value` line, with the file and line number it came from when
+/// it was read from a file.
+struct ConfigLine {
+    text: String,
+    origin: Option<(PathBuf, usize)>,
+}
+
+impl From<String> for ConfigLine {
+    fn from(text: String) -> Self {
+        ConfigLine { text, origin: None }
+    }
+}
+
+fn parse_config<L: Into<ConfigLine>>(lines: Vec<L>) -> Result<Config> {
     let mut builder = ConfigBuilder::new();
 
-    for arg in args.into_iter() {
+    for line in lines.into_iter().map(Into::into) {
+        let arg = &line.text;
         (|| -> Result<()> {
             let (key, value) = arg
                 .split_once('=')
                 .with_context(|| format!("invalid config on line: {}", arg))?;
             let keyword = ConfigKeyWord::from_str(key).unwrap();
+            builder.source(keyword.as_str(), ConfigSource::File);
             let res = match keyword {
                 ConfigKeyWord::InvalidKeyword => Err(anyhow!("use an invalid config option!")),
                 ConfigKeyWord::Debug => {
@@ -214,55 +839,218 @@ fn parse_config(args: Vec<String>) -> Result<Config> {
                     builder.max_age(val);
                     Ok(())
                 }
+                ConfigKeyWord::SlashBonus => {
+                    let val = value
+                        .parse::<f64>()
+                        .with_context(|| format!("invalid value for slash_bonus: {}", value))?;
+                    builder.slash_bonus(val);
+                    Ok(())
+                }
+                ConfigKeyWord::GapLeading => {
+                    let val = value
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|gap| *gap <= 0.0)
+                        .ok_or_else(|| anyhow!("invalid value for gap_leading: {}", value))?;
+                    builder.gap_leading(val);
+                    Ok(())
+                }
+                ConfigKeyWord::MaxLeadingGap => {
+                    let val = value
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|cap| *cap >= 0.0)
+                        .ok_or_else(|| anyhow!("invalid value for max_leading_gap: {}", value))?;
+                    builder.max_leading_gap(val);
+                    Ok(())
+                }
+                ConfigKeyWord::MaxPathLen => {
+                    let val = value
+                        .parse::<usize>()
+                        .with_context(|| format!("invalid value for max_path_len: {}", value))?;
+                    builder.max_path_len(val);
+                    Ok(())
+                }
+                ConfigKeyWord::StableOrder => {
+                    let val = value
+                        .parse::<bool>()
+                        .with_context(|| format!("invalid value for stable_order: {}", value))?;
+                    builder.stable_order(val);
+                    Ok(())
+                }
+                ConfigKeyWord::InsertDebounceMs => {
+                    let val = value.parse::<u64>().with_context(|| {
+                        format!("invalid value for insert_debounce_ms: {}", value)
+                    })?;
+                    builder.insert_debounce_ms(val);
+                    Ok(())
+                }
+                ConfigKeyWord::UsageStats => {
+                    let val = value
+                        .parse::<bool>()
+                        .with_context(|| format!("invalid value for usage_stats: {}", value))?;
+                    builder.usage_stats(val);
+                    Ok(())
+                }
+                ConfigKeyWord::BasenameBoost => {
+                    let val = value
+                        .parse::<f64>()
+                        .with_context(|| format!("invalid value for basename_boost: {}", value))?;
+                    builder.basename_boost(val);
+                    Ok(())
+                }
+                ConfigKeyWord::DepthPenalty => {
+                    let val = value
+                        .parse::<f64>()
+                        .with_context(|| format!("invalid value for depth_penalty: {}", value))?;
+                    builder.depth_penalty(val);
+                    Ok(())
+                }
+                ConfigKeyWord::IdleDecayRate => {
+                    let val = value
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|rate| (0.0..1.0).contains(rate))
+                        .ok_or_else(|| anyhow!("invalid value for idle_decay_rate: {}", value))?;
+                    builder.idle_decay_rate(val);
+                    Ok(())
+                }
+                ConfigKeyWord::ExcludeHome => {
+                    let val = value
+                        .parse::<bool>()
+                        .with_context(|| format!("invalid value for exclude_home: {}", value))?;
+                    builder.exclude_home(val);
+                    Ok(())
+                }
+                ConfigKeyWord::RequireDir => {
+                    let val = value
+                        .parse::<bool>()
+                        .with_context(|| format!("invalid value for require_dir: {}", value))?;
+                    builder.require_dir(val);
+                    Ok(())
+                }
+                ConfigKeyWord::TrackInode => {
+                    let val = value
+                        .parse::<bool>()
+                        .with_context(|| format!("invalid value for track_inode: {}", value))?;
+                    builder.track_inode(val);
+                    Ok(())
+                }
+                ConfigKeyWord::FoldCase => {
+                    let val = value
+                        .parse::<bool>()
+                        .with_context(|| format!("invalid value for fold_case: {}", value))?;
+                    builder.fold_case(val);
+                    Ok(())
+                }
+                ConfigKeyWord::ExactPath => {
+                    let val = value
+                        .parse::<bool>()
+                        .with_context(|| format!("invalid value for exact_path: {}", value))?;
+                    builder.exact_path(val);
+                    Ok(())
+                }
+                ConfigKeyWord::PathMap => {
+                    let mut map = vec![];
+                    for pair in value
+                        .trim_matches(|p| p == '[' || p == ']')
+                        .split_terminator(',')
+                    {
+                        let (from, to) = pair
+                            .split_once("=>")
+                            .ok_or_else(|| anyhow!("invalid path_map entry: {}", pair))?;
+                        map.push((
+                            expand_path(from.trim())?.to_string_lossy().into_owned(),
+                            expand_path(to.trim())?.to_string_lossy().into_owned(),
+                        ));
+                    }
+                    builder.path_map(map);
+                    Ok(())
+                }
+                ConfigKeyWord::Weight(prefix) => {
+                    let val = value
+                        .parse::<f64>()
+                        .with_context(|| format!("invalid value for weight: {}", value))?;
+                    builder.weight(expand_path(prefix)?, val);
+                    Ok(())
+                }
+                ConfigKeyWord::DatafileFormat => {
+                    builder.datafile_format(value.parse()?);
+                    Ok(())
+                }
+                ConfigKeyWord::DatafileSort => {
+                    builder.datafile_sort(value.parse()?);
+                    Ok(())
+                }
                 ConfigKeyWord::Datafile => {
-                    let path = Path::new(value);
+                    let path = expand_path(value)?;
                     if path.is_dir() {
                         return Err(anyhow!("invalid config value for datafile: {}", value));
                     }
-                    builder.datafile(path.display().to_string());
+                    builder.datafile(path);
+                    Ok(())
+                }
+                ConfigKeyWord::ExcludeMode => {
+                    builder.exclude_mode(value.parse()?);
+                    Ok(())
+                }
+                ConfigKeyWord::TiePolicy => {
+                    builder.tie_policy(value.parse()?);
                     Ok(())
                 }
                 ConfigKeyWord::ExcludeDirs => {
-                    let dirs = value
+                    // Directories that do not exist yet are kept, so they
+                    // are excluded from the moment they are created.
+                    let mut dirs = vec![];
+                    for dir in value
                         .trim_matches(|p| p == '[' || p == ']')
                         .split_terminator(',')
-                        .filter(|&x| {
-                            if !x.is_empty() {
-                                let path = Path::new(x);
-                                if path.is_dir() || path.is_symlink() {
-                                    return true;
-                                }
-                                return false;
-                            }
-                            false
-                        });
-                    let paths = dirs
-                        .map(|dir| String::from_str(dir).unwrap())
-                        .collect::<Vec<String>>();
-                    builder.exclude_dirs(paths.to_vec());
+                        .map(str::trim)
+                        .filter(|dir| !dir.is_empty())
+                    {
+                        dirs.push(expand_path(dir)?.to_string_lossy().into_owned());
+                    }
+                    builder.exclude_dirs(dirs);
                     Ok(())
                 }
             };
             res
         })()
-        .with_context(|| format!("invalid config option for {}", arg))?;
+        .with_context(|| match &line.origin {
+            Some((path, line_num)) => format!(
+                "{}:{}: invalid config option for {}",
+                path.display(),
+                line_num,
+                arg
+            ),
+            None => format!("invalid config option for {}", arg),
+        })?;
     }
 
     Ok(builder.build())
 }
+#[cfg(test)]
 fn read_config<R: Read>(config: R) -> Result<Config> {
+    parse_config(read_config_lines(config, Path::new("config"))?)
+}
+
+/// Lines of a config file read from `path`, numbered for error messages.
+fn read_config_lines<R: Read>(config: R, path: &Path) -> Result<Vec<ConfigLine>> {
     let reader = BufReader::new(config);
     let mut args = vec![];
-    for line in reader.lines() {
+    for (i, line) in reader.lines().enumerate() {
         let line = line.context("failed to read config line")?;
         let line = line.trim();
         // omit comments and empty lines
         if line.is_empty() || line.starts_with('#') {
             continue;
         }
-        args.push(line.to_string());
+        args.push(ConfigLine {
+            text: line.to_string(),
+            origin: Some((path.to_path_buf(), i + 1)),
+        });
     }
-    parse_config(args)
+    Ok(args)
 }
 
 #[cfg(test)]
@@ -293,11 +1081,30 @@ exclude_dirs=[/tmp,/usr]
         )
         .unwrap();
         assert_eq!(config.max_age, 5000);
-        assert_eq!(config.datafile, "~/.zcddata");
+        assert_eq!(config.datafile, home_dir().unwrap().join(".zcddata"));
         assert!(config.debug);
         assert_eq!(config.exclude_dirs.len(), 2);
     }
 
+    #[test]
+    fn exclude_dirs_are_expanded_like_other_paths() {
+        let config = parse_config(vec![
+            "exclude_dirs=[ ~/scratch/ , /does/not/exist/yet/,]".to_string()
+        ])
+        .unwrap();
+        let scratch = home_dir().unwrap().join("scratch");
+        assert_eq!(
+            config.exclude_dirs,
+            vec![
+                scratch.to_string_lossy().into_owned(),
+                "/does/not/exist/yet".to_string()
+            ]
+        );
+        assert!(config.is_excluded(&format!("{}/tmp", scratch.display())));
+        assert!(config.is_excluded("/does/not/exist/yet"));
+        assert!(!config.is_excluded("/does/not/exist/yetagain"));
+    }
+
     #[test]
     fn test_config() {
         let mut data_file = config_dir().unwrap();
@@ -306,14 +1113,278 @@ exclude_dirs=[/tmp,/usr]
             .exclude_dirs(vec![Path::new("~/.config").display().to_string()])
             .max_age(2000)
             .debug(true)
-            .datafile(data_file.display().to_string())
+            .datafile(data_file.clone())
             .build();
         assert_eq!(config.max_age, 2000);
-        assert_eq!(config.datafile, data_file.display().to_string());
+        assert_eq!(config.datafile, data_file);
         assert!(config.debug);
         assert_eq!(
             config.exclude_dirs,
             vec![Path::new("~/.config").display().to_string()]
         );
     }
+
+    #[test]
+    fn state_dir_prefers_an_absolute_xdg_state_home() {
+        let home = Some(PathBuf::from("/home/u"));
+        assert_eq!(
+            state_dir_from(Some("/var/state".into()), home.clone()),
+            Some(PathBuf::from("/var/state/zcd"))
+        );
+        assert_eq!(
+            state_dir_from(Some("relative".into()), home.clone()),
+            Some(PathBuf::from("/home/u/.local/state/zcd"))
+        );
+        assert_eq!(
+            state_dir_from(None, home),
+            Some(PathBuf::from("/home/u/.local/state/zcd"))
+        );
+        assert_eq!(state_dir_from(None, None), None);
+    }
+
+    #[test]
+    fn slash_bonus_defaults_and_parses() {
+        let config = parse_config(Vec::<String>::new()).unwrap();
+        assert_eq!(config.slash_bonus, SCORE_MATCH_SLASH);
+        let config = parse_config(vec!["slash_bonus=1.5".to_string()]).unwrap();
+        assert_eq!(config.slash_bonus, 1.5);
+        assert!(parse_config(vec!["slash_bonus=lots".to_string()]).is_err());
+    }
+
+    #[test]
+    fn leading_gap_keys_default_to_fzy_and_validate() {
+        let config = parse_config(Vec::<String>::new()).unwrap();
+        assert_eq!(config.gap_leading, SCORE_GAP_LEADING);
+        assert_eq!(config.max_leading_gap, f64::INFINITY);
+        let config = parse_config(vec![
+            "gap_leading=-0.001".to_string(),
+            "max_leading_gap=0.5".to_string(),
+        ])
+        .unwrap();
+        assert_eq!((config.gap_leading, config.max_leading_gap), (-0.001, 0.5));
+        assert!(parse_config(vec!["gap_leading=0.1".to_string()]).is_err());
+        assert!(parse_config(vec!["max_leading_gap=-1".to_string()]).is_err());
+    }
+
+    #[test]
+    fn later_config_files_override_earlier_keys() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().join("base");
+        let overlay = dir.path().join("overlay");
+        fs::write(&base, "max_age=1000\ndebug=true\n").unwrap();
+        fs::write(&overlay, "max_age=9000\n").unwrap();
+
+        let config = load_config_from_paths(&[&base, &overlay]).unwrap();
+        assert_eq!(config.max_age, 9000, "overlay should win");
+        assert!(
+            config.debug,
+            "keys absent from the overlay keep the base value"
+        );
+    }
+
+    #[test]
+    fn a_missing_overlay_fails_the_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().join("base");
+        let overlay = dir.path().join("overlay");
+        let default = dir.path().join("default");
+        fs::write(&base, "max_age=1000\n").unwrap();
+        let list = env::join_paths([&base, &overlay]).unwrap();
+
+        let files = config_files_from(Some(list), Some(default.clone()));
+        assert_eq!(files, [base.clone(), overlay.clone()]);
+        let err = load_config_from_paths(&files).unwrap_err();
+        assert!(
+            err.to_string().contains(&overlay.display().to_string()),
+            "{err:#}"
+        );
+
+        let single = config_files_from(Some(overlay.into_os_string()), Some(default.clone()));
+        assert_eq!(
+            single,
+            vec![default.clone()],
+            "a lone missing path falls back"
+        );
+        assert_eq!(config_files_from(None, Some(default.clone())), [default]);
+    }
+
+    #[test]
+    fn datafile_env_override_takes_precedence() {
+        let mut config = parse_config(vec!["datafile=/from/config".to_string()]).unwrap();
+        apply_datafile_override(&mut config, None).unwrap();
+        assert_eq!(config.datafile, Path::new("/from/config"));
+        apply_datafile_override(&mut config, Some(OsString::new())).unwrap();
+        assert_eq!(config.datafile, Path::new("/from/config"));
+        apply_datafile_override(&mut config, Some("~/from/env".into())).unwrap();
+        assert_eq!(config.datafile, home_dir().unwrap().join("from/env"));
+    }
+
+    #[test]
+    fn sources_distinguish_default_file_and_env() {
+        let mut config = parse_config(vec![
+            "max_age=100".to_string(),
+            "datafile=/from/config".to_string(),
+        ])
+        .unwrap();
+        assert_eq!(config.source("max_age"), ConfigSource::File);
+        assert_eq!(config.source("datafile"), ConfigSource::File);
+        assert_eq!(config.source("slash_bonus"), ConfigSource::Default);
+
+        apply_datafile_override(&mut config, Some("/from/env".into())).unwrap();
+        let (_, value, source) = config
+            .entries()
+            .into_iter()
+            .find(|(key, ..)| *key == "datafile")
+            .unwrap();
+        assert_eq!(value, "/from/env");
+        assert_eq!(source, ConfigSource::Env);
+    }
+
+    #[test]
+    fn entries_read_back_as_the_same_config() {
+        let config = parse_config(vec![
+            "max_age=1234".to_string(),
+            "datafile=/tmp/zcd/data".to_string(),
+            "datafile_format=kv".to_string(),
+            "exclude_dirs=[/tmp,/var/cache]".to_string(),
+            "tie_policy=shortest".to_string(),
+            "idle_decay_rate=0.25".to_string(),
+            "weight:/srv=0.5".to_string(),
+            "weight:/work=2".to_string(),
+            "path_map=[/workspace=>/home/me/proj,/mnt=>/media]".to_string(),
+        ])
+        .unwrap();
+        let lines = |config: &Config| -> Vec<String> {
+            config
+                .entries()
+                .into_iter()
+                .map(|(key, value, _)| format!("{}={}", key, value))
+                .collect()
+        };
+        let written = lines(&config);
+        assert!(
+            written.contains(&"weight:/srv=0.5".to_string()),
+            "{written:?}"
+        );
+        assert!(
+            written.contains(&"weight:/work=2".to_string()),
+            "{written:?}"
+        );
+
+        let reparsed = parse_config(written.clone()).unwrap();
+        assert_eq!(lines(&reparsed), written);
+        assert_eq!(reparsed.weights, config.weights);
+        assert_eq!(reparsed.path_map, config.path_map);
+    }
+
+    #[test]
+    fn path_map_rewrites_whole_component_prefixes() {
+        let config = parse_config(vec![
+            "path_map=[/workspace=>~/proj,/workspace/vendor=>/opt/vendor]".to_string(),
+        ])
+        .unwrap();
+        let proj = home_dir().unwrap().join("proj");
+        assert_eq!(config.map_path("/workspace"), proj.to_str().unwrap());
+        assert_eq!(
+            config.map_path("/workspace/src"),
+            proj.join("src").to_str().unwrap()
+        );
+        assert_eq!(config.map_path("/workspace/vendor/x"), "/opt/vendor/x");
+        assert!(matches!(
+            config.map_path("/workspace-old"),
+            Cow::Borrowed("/workspace-old")
+        ));
+        assert!(parse_config(vec!["path_map=[/a->/b]".to_string()]).is_err());
+    }
+
+    #[test]
+    fn generate_dry_run_prints_the_template_without_writing() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config_file = temp_dir.path().join("zcd").join("config");
+        let mut out = Vec::new();
+        write_default_config(&config_file, true, &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), DEFAULT_CONFIG);
+        assert!(!config_file.exists());
+        assert!(!config_file.parent().unwrap().exists());
+
+        let mut out = Vec::new();
+        write_default_config(&config_file, false, &mut out).unwrap();
+        assert_eq!(fs::read_to_string(&config_file).unwrap(), DEFAULT_CONFIG);
+    }
+
+    #[test]
+    fn errors_name_the_config_file_and_line() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().join("base");
+        let local = dir.path().join("local");
+        fs::write(&base, "max_age=100\n").unwrap();
+        fs::write(&local, "# overrides\n\nmax_age=200\nslash_bonus=lots\n").unwrap();
+
+        let err = load_config_from_paths(&[&base, &local]).unwrap_err();
+        let message = format!("{:#}", err);
+        assert!(
+            message.starts_with(&format!("{}:4: ", local.display())),
+            "got: {message}"
+        );
+        assert!(message.contains("slash_bonus=lots"), "got: {message}");
+    }
+
+    #[test]
+    fn weight_lines_are_expanded_and_later_ones_win() {
+        let config = parse_config(vec![
+            "weight:~/work=2.0".to_string(),
+            "weight:/srv=0.5".to_string(),
+            "weight:~/work=3".to_string(),
+        ])
+        .unwrap();
+        assert_eq!(
+            config.weights,
+            vec![
+                (PathBuf::from("/srv"), 0.5),
+                (home_dir().unwrap().join("work"), 3.0)
+            ]
+        );
+        assert!(parse_config(vec!["weight:/srv=heavy".to_string()]).is_err());
+    }
+
+    #[test]
+    fn database_reads_and_writes_the_overridden_datafile() {
+        use crate::db::{Database, OpsDelegate};
+
+        let dir = tempfile::tempdir().unwrap();
+        let configured = dir.path().join("configured");
+        let overridden = dir.path().join("overridden");
+        fs::write(&overridden, "/seeded|3.0|100\n").unwrap();
+        let mut config = parse_config(vec![format!("datafile={}", configured.display())]).unwrap();
+        apply_datafile_override(&mut config, Some(overridden.clone().into())).unwrap();
+
+        let mut db = Database::with_config(config).unwrap();
+        db.insert_or_update("/visited".into());
+        db.save().unwrap();
+
+        let text = fs::read_to_string(&overridden).unwrap();
+        assert!(text.contains("/seeded|3.0|100"), "existing data was read");
+        assert!(text.contains("/visited|1.0|"), "new data was written");
+        assert!(!configured.exists(), "the configured datafile is untouched");
+    }
+
+    #[test]
+    fn datafile_is_expanded_and_absolute_after_parse() {
+        let config = parse_config(vec!["datafile=~/data/zcd".to_string()]).unwrap();
+        assert!(config.datafile.is_absolute());
+        assert_eq!(config.datafile, home_dir().unwrap().join("data/zcd"));
+
+        let config = parse_config(vec!["datafile=relative/zcd".to_string()]).unwrap();
+        assert!(config.datafile.is_absolute());
+        assert!(config.datafile.ends_with("relative/zcd"));
+    }
+
+    #[test]
+    fn expand_path_substitutes_env_components() {
+        let home = home_dir().unwrap();
+        assert_eq!(expand_path("~").unwrap(), home);
+        assert_eq!(expand_path("$HOME/.zcd").unwrap(), home.join(".zcd"));
+        assert_eq!(expand_path("${HOME}/.zcd").unwrap(), home.join(".zcd"));
+        assert!(expand_path("$ZCD_SURELY_UNSET_VAR/x").is_err());
+    }
 }
@@ -0,0 +1,75 @@
+//! Named bookmarks, kept in a `<datafile>.bookmarks` sidecar as one
+//! `name|path` line per bookmark.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Context, Result};
+
+use super::data::write_file;
+
+pub type Bookmarks = BTreeMap<String, String>;
+
+/// Query prefix that selects a bookmark by name, e.g. `@work`.
+pub const BOOKMARK_PREFIX: char = '@';
+
+pub fn load(path: &Path) -> Result<Bookmarks> {
+    if !path.exists() {
+        return Ok(Bookmarks::new());
+    }
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("failed to read bookmarks {}", path.display()))?;
+    let mut bookmarks = Bookmarks::new();
+    for (line_num, line) in text.lines().enumerate() {
+        if line.is_empty() {
+            continue;
+        }
+        let (name, target) = line
+            .split_once('|')
+            .ok_or_else(|| anyhow!("invalid bookmark at line {}: {}", line_num + 1, line))?;
+        bookmarks.insert(name.to_string(), target.to_string());
+    }
+    Ok(bookmarks)
+}
+
+pub fn save(path: &Path, bookmarks: &Bookmarks) -> Result<()> {
+    let mut buffer = String::new();
+    for (name, target) in bookmarks {
+        buffer.push_str(&format!("{}|{}\n", name, target));
+    }
+    write_file(path, buffer)
+}
+
+/// Bookmark names are single words so `@name` is unambiguous on the
+/// command line and `|` stays free as the field separator.
+pub fn validate_name(name: &str) -> Result<()> {
+    if name.is_empty()
+        || name.starts_with(BOOKMARK_PREFIX)
+        || name.contains(|c: char| c == '|' || c.is_whitespace())
+    {
+        bail!("invalid bookmark name: {:?}", name);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test_bookmark {
+    use super::*;
+
+    #[test]
+    fn roundtrip_and_name_validation() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("zcddata.bookmarks");
+        let mut bookmarks = Bookmarks::new();
+        bookmarks.insert("work".into(), "/home/u/work".into());
+        bookmarks.insert("pipes".into(), "/odd|dir".into());
+        save(&path, &bookmarks).unwrap();
+        assert_eq!(load(&path).unwrap(), bookmarks);
+
+        assert!(validate_name("work").is_ok());
+        for bad in ["", "@work", "a b", "a|b"] {
+            assert!(validate_name(bad).is_err(), "{bad:?} should be rejected");
+        }
+    }
+}
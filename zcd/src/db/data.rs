@@ -1,52 +1,244 @@
 //! Datafile I/O.
 //!
-//! One plain-text format, shared with the original `z` tool so the data
-//! stays portable across z-like tools: one entry per line,
-//! `path|rank|last_accessed_epoch`, sorted by rank on write.
+//! The datafile uses one plain-text format, shared with the original `z`
+//! tool so the data stays portable across z-like tools: one entry per
+//! line, `path|rank|last_accessed_epoch`, sorted by rank on write.
+//!
+//! Import/export additionally understand [`DataFormat::Kv`], a
+//! `path=<p>;rank=<r>;last=<t>` line format sorted by path, which keeps
+//! diffs of exported history minimal. `datafile_format=kv` uses it for the
+//! datafile itself. [`DataFormat::Tsv`] reads and writes tab-separated
+//! columns from other tools, in an order given by [`TsvColumns`].
 
-use std::borrow::Cow;
+use std::fmt;
 use std::fs;
 use std::fs::File;
-use std::io::{BufRead, BufReader, Read};
-use std::path::{Path, PathBuf};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::Path;
 
 use super::dir::{Dir, DirList, Epoch, Ranking};
-use anyhow::{anyhow, Context, Result};
+use anyhow::{anyhow, bail, Context, Error, Result};
 use itertools::Itertools;
+use std::str::FromStr;
 
-pub fn expand_path<P: AsRef<Path>>(p: P) -> Option<PathBuf> {
-    let path = p.as_ref();
-    if !path.starts_with("~") {
-        return Some(path.to_path_buf());
+/// Line formats accepted by import/export.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DataFormat {
+    /// `path|rank|last_accessed`, the datafile format.
+    #[default]
+    Z,
+    /// `path=<p>;rank=<r>;last=<t>`, ordered by path.
+    Kv,
+    /// Tab-separated `path`, `visits` and `last_accessed` columns.
+    Tsv,
+}
+
+impl FromStr for DataFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "z" => Ok(DataFormat::Z),
+            "kv" => Ok(DataFormat::Kv),
+            "tsv" => Ok(DataFormat::Tsv),
+            _ => bail!("unknown format {:?}; expected z, kv or tsv", s),
+        }
+    }
+}
+
+impl fmt::Display for DataFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            DataFormat::Z => "z",
+            DataFormat::Kv => "kv",
+            DataFormat::Tsv => "tsv",
+        })
+    }
+}
+
+/// What one TSV column holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TsvColumn {
+    Path,
+    /// Visit count, stored as the rank.
+    Visits,
+    LastAccessed,
+    /// A column zcd has no use for, written `_`.
+    Skip,
+}
+
+/// Column layout of a TSV file, e.g. `path,visits,last_accessed`. Only
+/// `path` is required; visits default to 1 and the last visit to 0.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TsvColumns(Vec<TsvColumn>);
+
+impl Default for TsvColumns {
+    fn default() -> Self {
+        TsvColumns(vec![
+            TsvColumn::Path,
+            TsvColumn::Visits,
+            TsvColumn::LastAccessed,
+        ])
+    }
+}
+
+impl FromStr for TsvColumns {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let columns: Vec<TsvColumn> = s
+            .split(',')
+            .map(|name| match name.trim() {
+                "path" => Ok(TsvColumn::Path),
+                "visits" => Ok(TsvColumn::Visits),
+                "last_accessed" => Ok(TsvColumn::LastAccessed),
+                "_" => Ok(TsvColumn::Skip),
+                other => bail!(
+                    "unknown column {:?}; expected path, visits, last_accessed or _",
+                    other
+                ),
+            })
+            .collect::<Result<_>>()?;
+        for column in [TsvColumn::Path, TsvColumn::Visits, TsvColumn::LastAccessed] {
+            let count = columns.iter().filter(|&&c| c == column).count();
+            if count > 1 || (column == TsvColumn::Path && count == 0) {
+                bail!("columns need exactly one path and at most one of each other field");
+            }
+        }
+        Ok(TsvColumns(columns))
+    }
+}
+
+/// Line order of a written z-format datafile.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineOrder {
+    /// Best rank first, as z writes it.
+    #[default]
+    Rank,
+    /// By path, so a changed entry only touches its own line in a diff.
+    Path,
+}
+
+impl FromStr for LineOrder {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "rank" => Ok(LineOrder::Rank),
+            "path" => Ok(LineOrder::Path),
+            _ => bail!("unknown sort order {:?}; expected rank or path", s),
+        }
     }
-    if path == Path::new("~") {
-        return dirs::home_dir();
+}
+
+impl fmt::Display for LineOrder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            LineOrder::Rank => "rank",
+            LineOrder::Path => "path",
+        })
+    }
+}
+
+/// Field separator of a z-format file. zcd writes `|`; legacy files from
+/// other tools may use another single character, such as a tab.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Delimiter(pub char);
+
+impl Default for Delimiter {
+    fn default() -> Self {
+        Delimiter('|')
+    }
+}
+
+impl FromStr for Delimiter {
+    type Err = Error;
+
+    /// A single character, or `tab` / `\t` for a tab.
+    fn from_str(s: &str) -> Result<Self> {
+        if s == "tab" || s == "\\t" {
+            return Ok(Delimiter('\t'));
+        }
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) if c != '\n' => Ok(Delimiter(c)),
+            _ => bail!("invalid delimiter {:?}; expected one character or tab", s),
+        }
     }
-    dirs::home_dir().map(|mut home| {
-        home.push(path.strip_prefix("~").unwrap());
-        home
-    })
 }
 
-fn resolve_path<P: AsRef<Path>>(p: P) -> Result<PathBuf> {
-    expand_path(p.as_ref())
-        .with_context(|| format!("cannot resolve home directory for {}", p.as_ref().display()))
+impl DataFormat {
+    pub fn to_bytes(self, data: &DirList) -> Vec<u8> {
+        match self {
+            DataFormat::Z => to_bytes(data),
+            DataFormat::Kv => to_kv_bytes(data),
+            DataFormat::Tsv => to_tsv_bytes(data),
+        }
+    }
+
+    /// Serialize with z-format lines in `order`. Kv and tsv are always by
+    /// path.
+    pub fn to_bytes_ordered(self, data: &DirList, order: LineOrder) -> Vec<u8> {
+        match self {
+            DataFormat::Z => to_bytes_ordered(data, order),
+            DataFormat::Kv => to_kv_bytes(data),
+            DataFormat::Tsv => to_tsv_bytes(data),
+        }
+    }
+
+    pub fn from_bytes<T: Read>(self, f: T) -> Result<DirList<'static>> {
+        match self {
+            DataFormat::Z => from_bytes(f),
+            DataFormat::Kv => from_kv_bytes(f),
+            DataFormat::Tsv => from_tsv_bytes(f, &TsvColumns::default()),
+        }
+    }
 }
 
 pub fn open_file<P: AsRef<Path>>(p: P) -> Result<File> {
-    let path = resolve_path(p)?;
-    File::open(&path).with_context(|| format!("Failed to load {}", path.display()))
+    let path = p.as_ref();
+    File::open(path).with_context(|| format!("Failed to load {}", path.display()))
 }
 
+/// Write `c` to a fresh temporary file next to `p` and rename it over
+/// `p`, so readers, including a mapped datafile, see either the old or
+/// the new file and never one cut short mid-write. Each call stages in its
+/// own uniquely named file, so concurrent writers cannot publish each
+/// other's half-written data; the last rename wins. A symlink at `p` is
+/// followed and the file it points at is replaced, keeping its mode.
 pub fn write_file<P: AsRef<Path>, C: AsRef<[u8]>>(p: P, c: C) -> Result<()> {
-    let path = resolve_path(p)?;
-    fs::write(&path, c.as_ref()).with_context(|| format!("failed to write into {}", path.display()))
+    let path = p.as_ref();
+    let target = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let dir = match target.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let mut staged = tempfile::NamedTempFile::new_in(dir)
+        .with_context(|| format!("failed to create a temporary file in {}", dir.display()))?;
+    staged
+        .write_all(c.as_ref())
+        .with_context(|| format!("failed to write into {}", staged.path().display()))?;
+    if let Ok(meta) = fs::metadata(&target) {
+        staged.as_file().set_permissions(meta.permissions())?;
+    }
+    staged
+        .persist(&target)
+        .with_context(|| format!("failed to replace {}", target.display()))?;
+    Ok(())
 }
 
 /// Serialize entries in the z-compatible pipe format, best rank first.
 pub fn to_bytes(data: &DirList) -> Vec<u8> {
+    to_bytes_ordered(data, LineOrder::Rank)
+}
+
+/// [`to_bytes`] with the lines in `order`.
+pub fn to_bytes_ordered(data: &DirList, order: LineOrder) -> Vec<u8> {
     let mut buffer = String::new();
-    for dir in data.values().sorted_by(|a, b| Ord::cmp(&b, &a)) {
+    for dir in data.values().sorted_by(|a, b| match order {
+        LineOrder::Rank => Ord::cmp(&b, &a),
+        LineOrder::Path => a.path.cmp(&b.path),
+    }) {
         buffer.push_str(&format!(
             "{}|{:.1}|{}\n",
             dir.path, dir.rank, dir.last_accessed
@@ -58,6 +250,12 @@ pub fn to_bytes(data: &DirList) -> Vec<u8> {
 /// Parse a z-compatible datafile. Paths may contain `|`, so fields are
 /// split from the right.
 pub fn from_bytes<T: Read>(f: T) -> Result<DirList<'static>> {
+    from_bytes_delimited(f, Delimiter::default())
+}
+
+/// [`from_bytes`] with fields separated by `delimiter` instead of `|`.
+/// Only the last two fields are split off, so paths may contain it.
+pub fn from_bytes_delimited<T: Read>(f: T, delimiter: Delimiter) -> Result<DirList<'static>> {
     let mut dir_list = DirList::new();
     let reader = BufReader::new(f);
     for (line_num, line) in reader.lines().enumerate() {
@@ -65,15 +263,104 @@ pub fn from_bytes<T: Read>(f: T) -> Result<DirList<'static>> {
         if line.is_empty() {
             continue;
         }
-        let (path_str, rank, last_accessed) = (|| {
-            let mut fields = line.rsplitn(3, '|');
-            let last_accessed = fields.next()?;
-            let rank = fields.next()?;
-            let path_str = fields.next()?;
-            Some((path_str, rank, last_accessed))
-        })()
-        .ok_or_else(|| anyhow!("invalid entry at line {}: {}", line_num + 1, line))?;
+        let dir = parse_z_line(&line, line_num, delimiter)?;
+        dir_list.insert(dir.path.to_string(), dir);
+    }
+    Ok(dir_list)
+}
+
+/// [`from_bytes`] over data already in memory, such as a mapped
+/// datafile. Lines are sliced out of `bytes` instead of being copied into
+/// a line buffer first; each entry still allocates its own path.
+#[cfg(any(feature = "mmap", test))]
+pub fn from_slice(bytes: &[u8]) -> Result<DirList<'static>> {
+    let mut dir_list = DirList::new();
+    for (line_num, line) in bytes.split(|&b| b == b'\n').enumerate() {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        if line.is_empty() {
+            continue;
+        }
+        let line = std::str::from_utf8(line)
+            .with_context(|| format!("invalid UTF-8 at line {}", line_num + 1))?;
+        let dir = parse_z_line(line, line_num, Delimiter::default())?;
+        dir_list.insert(dir.path.to_string(), dir);
+    }
+    Ok(dir_list)
+}
+
+/// Map the z-format datafile at `path` into memory and parse it with
+/// [`from_slice`].
+#[cfg(feature = "mmap")]
+pub fn map_file(path: &Path) -> Result<DirList<'static>> {
+    let file = open_file(path)?;
+    // SAFETY: the map is only read while parsing and dropped right after.
+    // zcd never writes the datafile in place: `write_file` renames a new
+    // file over it, which leaves the mapped inode untouched. A process
+    // outside zcd truncating the file meanwhile could still fault the
+    // reader; mmap offers no protection against that.
+    let map = unsafe { memmap2::Mmap::map(&file) }
+        .with_context(|| format!("failed to map {}", path.display()))?;
+    from_slice(&map)
+}
+
+fn parse_z_line(line: &str, line_num: usize, delimiter: Delimiter) -> Result<Dir<'static>> {
+    let (path_str, rank, last_accessed) = (|| {
+        let mut fields = line.rsplitn(3, delimiter.0);
+        let last_accessed = fields.next()?;
+        let rank = fields.next()?;
+        let path_str = fields.next()?;
+        Some((path_str, rank, last_accessed))
+    })()
+    .ok_or_else(|| anyhow!("invalid entry at line {}: {}", line_num + 1, line))?;
+
+    let rank = rank
+        .parse::<Ranking>()
+        .with_context(|| format!("invalid rank at line {}: {}", line_num + 1, rank))?;
+    let last_accessed = last_accessed.parse::<Epoch>().with_context(|| {
+        format!(
+            "invalid last accessed at line {}: {}",
+            line_num + 1,
+            last_accessed
+        )
+    })?;
+
+    Ok(Dir::new(path_str.to_string())
+        .with_rank(rank)
+        .with_last_accessed(last_accessed))
+}
+
+/// Serialize entries as `path=<p>;rank=<r>;last=<t>`, ordered by path so
+/// the same data always yields the same bytes.
+pub fn to_kv_bytes(data: &DirList) -> Vec<u8> {
+    let mut buffer = String::new();
+    for dir in data.values().sorted_by(|a, b| a.path.cmp(&b.path)) {
+        buffer.push_str(&format!(
+            "path={};rank={:.1};last={}\n",
+            dir.path, dir.rank, dir.last_accessed
+        ));
+    }
+    buffer.into_bytes()
+}
 
+/// Parse [`to_kv_bytes`] output. Paths may contain `;`, so fields are
+/// split from the right.
+pub fn from_kv_bytes<T: Read>(f: T) -> Result<DirList<'static>> {
+    let mut dir_list = DirList::new();
+    let reader = BufReader::new(f);
+    for (line_num, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.is_empty() {
+            continue;
+        }
+        let invalid = || anyhow!("invalid entry at line {}: {}", line_num + 1, line);
+        let mut fields = line.rsplitn(3, ';');
+        let last_accessed = fields.next().and_then(|f| f.strip_prefix("last="));
+        let rank = fields.next().and_then(|f| f.strip_prefix("rank="));
+        let path_str = fields.next().and_then(|f| f.strip_prefix("path="));
+        let (Some(path_str), Some(rank), Some(last_accessed)) = (path_str, rank, last_accessed)
+        else {
+            return Err(invalid());
+        };
         let rank = rank
             .parse::<Ranking>()
             .with_context(|| format!("invalid rank at line {}: {}", line_num + 1, rank))?;
@@ -84,19 +371,70 @@ pub fn from_bytes<T: Read>(f: T) -> Result<DirList<'static>> {
                 last_accessed
             )
         })?;
-
         dir_list.insert(
             path_str.to_string(),
-            Dir {
-                path: Cow::Owned(path_str.into()),
-                rank,
-                last_accessed,
-            },
+            Dir::new(path_str.to_string())
+                .with_rank(rank)
+                .with_last_accessed(last_accessed),
         );
     }
     Ok(dir_list)
 }
 
+/// Serialize entries as `path<TAB>visits<TAB>last_accessed`, ordered by
+/// path.
+pub fn to_tsv_bytes(data: &DirList) -> Vec<u8> {
+    let mut buffer = String::new();
+    for dir in data.values().sorted_by(|a, b| a.path.cmp(&b.path)) {
+        buffer.push_str(&format!(
+            "{}\t{:.1}\t{}\n",
+            dir.path, dir.rank, dir.last_accessed
+        ));
+    }
+    buffer.into_bytes()
+}
+
+/// Parse tab-separated lines laid out as `columns`. Extra trailing
+/// columns are ignored.
+pub fn from_tsv_bytes<T: Read>(f: T, columns: &TsvColumns) -> Result<DirList<'static>> {
+    let mut dir_list = DirList::new();
+    let reader = BufReader::new(f);
+    for (line_num, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.is_empty() {
+            continue;
+        }
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() < columns.0.len() {
+            bail!(
+                "expected {} columns at line {}: {}",
+                columns.0.len(),
+                line_num + 1,
+                line
+            );
+        }
+        let mut dir = Dir::new(String::new());
+        for (column, field) in columns.0.iter().zip(&fields) {
+            match column {
+                TsvColumn::Path => dir.path = field.to_string().into(),
+                TsvColumn::Visits => {
+                    dir.rank = field.parse::<Ranking>().with_context(|| {
+                        format!("invalid visits at line {}: {}", line_num + 1, field)
+                    })?;
+                }
+                TsvColumn::LastAccessed => {
+                    dir.last_accessed = field.parse::<Epoch>().with_context(|| {
+                        format!("invalid last accessed at line {}: {}", line_num + 1, field)
+                    })?;
+                }
+                TsvColumn::Skip => {}
+            }
+        }
+        dir_list.insert(dir.path.to_string(), dir);
+    }
+    Ok(dir_list)
+}
+
 #[cfg(test)]
 mod test_data {
     use super::*;
@@ -117,6 +455,146 @@ mod test_data {
         assert!(list2.contains_key("/home/user/dev/sandbox/action-timer"));
     }
 
+    #[test]
+    fn slice_reader_matches_buffered_reader() {
+        let z_data = "/home/u/a|28|1626969287\r\n\n/odd|pipe|2.5|1626960591\n/last|1|0";
+        let buffered = from_bytes(z_data.as_bytes()).unwrap();
+        let sliced = from_slice(z_data.as_bytes()).unwrap();
+        assert_eq!(buffered.len(), 3);
+        assert!(buffered.diff(&sliced).is_empty());
+        assert!(from_slice(b"/broken|1\n").is_err());
+    }
+
+    #[test]
+    fn write_file_replaces_instead_of_truncating() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("zcddata");
+        fs::write(&path, "/old|1.0|1\n").unwrap();
+        let mut reader = open_file(&path).unwrap();
+        write_file(&path, "/new|2.0|2\n").unwrap();
+
+        let mut before = String::new();
+        reader.read_to_string(&mut before).unwrap();
+        assert_eq!(before, "/old|1.0|1\n", "an open reader keeps the old file");
+        assert_eq!(fs::read_to_string(&path).unwrap(), "/new|2.0|2\n");
+        assert_eq!(
+            fs::read_dir(dir.path()).unwrap().count(),
+            1,
+            "no staging file left"
+        );
+    }
+
+    #[test]
+    fn concurrent_writes_never_tear_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("zcddata");
+        let contents: Vec<String> = (0..8)
+            .map(|i| format!("/writer-{i}|1.0|1\n").repeat(2000))
+            .collect();
+        std::thread::scope(|scope| {
+            for text in &contents {
+                let path = &path;
+                scope.spawn(move || {
+                    for _ in 0..20 {
+                        write_file(path, text).unwrap();
+                    }
+                });
+            }
+        });
+        let written = fs::read_to_string(&path).unwrap();
+        assert!(
+            contents.contains(&written),
+            "the file holds one whole write"
+        );
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn write_file_follows_symlinks_and_keeps_the_mode() {
+        use std::os::unix::fs::{symlink, PermissionsExt};
+        let dir = tempfile::tempdir().unwrap();
+        let real = dir.path().join("real");
+        let link = dir.path().join("link");
+        fs::write(&real, "old").unwrap();
+        fs::set_permissions(&real, fs::Permissions::from_mode(0o640)).unwrap();
+        symlink(&real, &link).unwrap();
+
+        write_file(&link, "new").unwrap();
+        assert!(fs::symlink_metadata(&link)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(fs::read_to_string(&real).unwrap(), "new");
+        let mode = fs::metadata(&real).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o640);
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn mapped_reader_matches_buffered_reader() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("zcddata");
+        let lines: String = (0..500)
+            .map(|i| format!("/home/u/dir-{i}|{}.5|{}\n", i % 40, 1_600_000_000 + i))
+            .collect();
+        fs::write(&path, &lines).unwrap();
+        let mapped = map_file(&path).unwrap();
+        assert_eq!(mapped.len(), 500);
+        assert!(from_bytes(lines.as_bytes())
+            .unwrap()
+            .diff(&mapped)
+            .is_empty());
+
+        fs::write(&path, "").unwrap();
+        assert!(map_file(&path).unwrap().is_empty());
+    }
+
+    /// Rough timing of both z readers on a large datafile; run with
+    /// `cargo test --release --features mmap -- --ignored --nocapture`.
+    #[cfg(feature = "mmap")]
+    #[test]
+    #[ignore]
+    fn bench_mapped_against_buffered_reader() {
+        use std::time::Instant;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("zcddata");
+        let lines: String = (0..200_000)
+            .map(|i| {
+                format!(
+                    "/home/user/projects/group-{}/repo-{i}|{}.5|{}\n",
+                    i % 97,
+                    i % 40,
+                    i
+                )
+            })
+            .collect();
+        fs::write(&path, &lines).unwrap();
+        let start = Instant::now();
+        let buffered = from_bytes(open_file(&path).unwrap()).unwrap();
+        let buffered_time = start.elapsed();
+        let start = Instant::now();
+        let mapped = map_file(&path).unwrap();
+        let mapped_time = start.elapsed();
+        assert_eq!(buffered.len(), mapped.len());
+        println!("buffered {buffered_time:?}, mapped {mapped_time:?}");
+    }
+
+    #[test]
+    fn tab_delimited_import_keeps_delimiters_in_paths() {
+        let delimiter: Delimiter = "tab".parse().unwrap();
+        assert_eq!("\\t".parse::<Delimiter>().unwrap(), delimiter);
+        let data = "/home/u/a\t28\t1626969287\n/odd\tname|x\t2.5\t100\n";
+        let list = from_bytes_delimited(data.as_bytes(), delimiter).unwrap();
+        assert_eq!(list.len(), 2);
+        assert_eq!(list["/home/u/a"].rank, 28.0);
+        assert_eq!(list["/odd\tname|x"].last_accessed, 100);
+        assert!(from_bytes(data.as_bytes()).is_err());
+        for bad in ["", "ab", "\n"] {
+            assert!(bad.parse::<Delimiter>().is_err(), "{bad:?}");
+        }
+    }
+
     #[test]
     fn serialization_orders_by_rank_descending() {
         let data = "/low|1|100\n/high|50|100\n";
@@ -148,17 +626,54 @@ mod test_data {
     }
 
     #[test]
-    fn missing_file_fails_loudly() {
-        assert!(open_file(Path::new("/tmpaaasdfsdf/a_file_does_not_exist")).is_err());
+    fn kv_export_is_path_ordered_and_roundtrips() {
+        let list = from_bytes("/b|9|200\n/a;semi|1|100\n/c|3|300\n".as_bytes()).unwrap();
+        let bytes = DataFormat::Kv.to_bytes(&list);
+        assert_eq!(
+            String::from_utf8(bytes.clone()).unwrap(),
+            "path=/a;semi;rank=1.0;last=100\n\
+             path=/b;rank=9.0;last=200\n\
+             path=/c;rank=3.0;last=300\n"
+        );
+
+        let parsed = DataFormat::Kv.from_bytes(bytes.as_slice()).unwrap();
+        assert!(parsed.diff(&list).is_empty());
+        assert_eq!(
+            DataFormat::Kv.to_bytes(&parsed),
+            bytes,
+            "exports are byte-identical"
+        );
+        assert!(DataFormat::Kv.from_bytes("/a|1|100\n".as_bytes()).is_err());
     }
 
     #[test]
-    fn expand_tilde_paths() {
-        if let Ok(home) = std::env::var("HOME") {
-            let mut home_path = Path::new(&home).to_path_buf();
-            assert_eq!(home_path, expand_path(Path::new("~")).unwrap());
-            home_path.push(".config/zcd");
-            assert_eq!(home_path, expand_path(Path::new("~/.config/zcd")).unwrap());
+    fn tsv_import_follows_the_column_mapping() {
+        let columns: TsvColumns = "last_accessed,_,path,visits".parse().unwrap();
+        let tsv = "1600000000\tx\t/home/u/proj\t12\n1700000000\ty\t/srv/my dir\t3\textra\n";
+        let list = from_tsv_bytes(tsv.as_bytes(), &columns).unwrap();
+        assert_eq!(list.len(), 2);
+        let proj = &list["/home/u/proj"];
+        assert_eq!((proj.rank, proj.last_accessed), (12.0, 1_600_000_000));
+        assert_eq!(list["/srv/my dir"].rank, 3.0);
+
+        let bytes = DataFormat::Tsv.to_bytes(&list);
+        assert_eq!(
+            String::from_utf8(bytes.clone()).unwrap(),
+            "/home/u/proj\t12.0\t1600000000\n/srv/my dir\t3.0\t1700000000\n"
+        );
+        let parsed = DataFormat::Tsv.from_bytes(bytes.as_slice()).unwrap();
+        assert!(parsed.diff(&list).is_empty());
+
+        let path_only = from_tsv_bytes("/a\n".as_bytes(), &"path".parse().unwrap()).unwrap();
+        assert_eq!(path_only["/a"].rank, 1.0);
+        assert!(from_tsv_bytes("/a\n".as_bytes(), &columns).is_err());
+        for bad in ["visits", "path,path", "path,rank"] {
+            assert!(bad.parse::<TsvColumns>().is_err(), "{bad}");
         }
     }
+
+    #[test]
+    fn missing_file_fails_loudly() {
+        assert!(open_file(Path::new("/tmpaaasdfsdf/a_file_does_not_exist")).is_err());
+    }
 }
@@ -6,10 +6,14 @@ use std::fmt;
 use std::fmt::Display;
 use std::ops::{Deref, DerefMut};
 use std::path::Path;
+use std::str::FromStr;
 use std::time::SystemTime;
 
+use anyhow::{bail, Error, Result};
 use itertools::Itertools;
 
+use crate::fuzzy::{Matcher, SCORE_MAX};
+
 pub type Ranking = f64;
 pub type Epoch = u64;
 
@@ -22,12 +26,96 @@ const WEEK: u64 = 7 * DAY;
 const AGE_DECAY: f64 = 0.9;
 const AGE_DROP_THRESHOLD: f64 = 1.0;
 
+/// Path components a match may have before [`QueryOptions::depth_penalty`]
+/// starts to apply; `/home/user/src/app` is still free.
+const DEPTH_PENALTY_FREE: usize = 4;
+
 #[derive(Debug, Clone)]
 pub struct Dir<'a> {
     pub path: Cow<'a, str>,
     /// Accumulated visit weight (+1 per visit, decayed by aging).
     pub rank: Ranking,
     pub last_accessed: Epoch,
+    /// Exempt from decay, aging and `purge-missing`; persisted outside
+    /// the datafile, which stays z-compatible.
+    pub pinned: bool,
+    /// Whether the entry was ever visited or only imported. Persisted
+    /// outside the datafile, like `pinned`.
+    pub origin: Origin,
+}
+
+/// How an entry got into the database.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Origin {
+    /// Recorded by a real visit, or imported and visited since.
+    #[default]
+    Visited,
+    /// Brought in by `import` and not visited since.
+    Imported,
+}
+
+impl FromStr for Origin {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "visited" => Ok(Origin::Visited),
+            "imported" => Ok(Origin::Imported),
+            _ => bail!("unknown origin {:?}; expected visited or imported", s),
+        }
+    }
+}
+
+impl Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Origin::Visited => "visited",
+            Origin::Imported => "imported",
+        })
+    }
+}
+
+impl<'a> Dir<'a> {
+    /// A directory seen once, with no recorded access time. Chain
+    /// `with_rank`/`with_last_accessed` to fill in stored values.
+    pub fn new<P: Into<Cow<'a, str>>>(path: P) -> Self {
+        Dir {
+            path: path.into(),
+            rank: 1.0,
+            last_accessed: 0,
+            pinned: false,
+            origin: Origin::Visited,
+        }
+    }
+
+    pub fn with_rank(mut self, rank: Ranking) -> Self {
+        self.rank = rank;
+        self
+    }
+
+    pub fn with_last_accessed(mut self, last_accessed: Epoch) -> Self {
+        self.last_accessed = last_accessed;
+        self
+    }
+
+    /// This entry with its path copied, free of any borrowed data.
+    pub fn into_owned(self) -> Dir<'static> {
+        Dir {
+            path: Cow::Owned(self.path.into_owned()),
+            rank: self.rank,
+            last_accessed: self.last_accessed,
+            pinned: self.pinned,
+            origin: self.origin,
+        }
+    }
+
+    /// Record a visit at `now`: one more unit of rank and a fresh access
+    /// time. An imported entry counts as visited from here on.
+    pub fn touch(&mut self, now: Epoch) {
+        self.rank += 1.0;
+        self.last_accessed = now;
+        self.origin = Origin::Visited;
+    }
 }
 
 impl Ord for Dir<'_> {
@@ -87,20 +175,409 @@ impl<'a, const N: usize> From<[(String, Dir<'a>); N]> for DirList<'a> {
     }
 }
 
+/// Entry-level differences between an old and a new [`DirList`], each
+/// sorted by path.
+#[derive(Debug, Default, PartialEq)]
+pub struct DirListDiff {
+    /// Paths only in the new list.
+    pub added: Vec<String>,
+    /// Paths only in the old list.
+    pub removed: Vec<String>,
+    /// Paths in both whose rank differs: `(path, old rank, new rank)`.
+    pub changed: Vec<(String, Ranking, Ranking)>,
+}
+
+impl DirListDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Entries sharing their first path components, rolled up by
+/// [`DirList::group_by`].
+#[derive(Debug, PartialEq)]
+pub struct DirGroup {
+    pub prefix: String,
+    pub count: usize,
+    /// Sum of the stored ranks (visits) of the grouped entries.
+    pub rank: Ranking,
+}
+
+/// Summary of a [`DirList`], computed by [`DirList::stats`].
+#[derive(Debug, Default, PartialEq)]
+pub struct DbStats {
+    pub entries: usize,
+    /// Sum of stored ranks, i.e. visits still counted after aging.
+    pub total_visits: Ranking,
+    /// Rank extremes and mean; zero for an empty list.
+    pub min_rank: Ranking,
+    pub max_rank: Ranking,
+    pub mean_rank: Ranking,
+    pub oldest: Option<Epoch>,
+    pub newest: Option<Epoch>,
+    /// Entries whose directory no longer exists.
+    pub missing: usize,
+}
+
 impl DirList<'_> {
     pub fn new() -> Self {
         DirList(HashMap::new())
     }
 
+    /// [`OpsDelegate::insert_or_update`] with an explicit visit time, for
+    /// importers and tests that must not depend on the clock.
+    pub fn insert_at(&mut self, p: Cow<'_, str>, at: Epoch) {
+        match self.entry(p.to_string()) {
+            Entry::Vacant(e) => {
+                e.insert(Dir::new(p.into_owned()).with_last_accessed(at));
+            }
+            Entry::Occupied(mut e) => e.get_mut().touch(at),
+        }
+    }
+
+    /// The stored path that equals `path` ignoring case, if any.
+    pub fn case_variant(&self, path: &str) -> Option<&str> {
+        let key = case_key(path);
+        self.keys()
+            .find(|stored| case_key(stored) == key)
+            .map(String::as_str)
+    }
+
+    /// Merge entries whose paths differ only in case into the spelling
+    /// with the highest rank. Their visits were separate, so ranks add up.
+    /// Returns the number of entries merged away.
+    pub fn fold_case(&mut self) -> usize {
+        let mut groups: HashMap<String, Vec<String>> = HashMap::new();
+        for path in self.keys() {
+            groups.entry(case_key(path)).or_default().push(path.clone());
+        }
+        let mut merged = 0;
+        for mut variants in groups.into_values().filter(|v| v.len() > 1) {
+            variants.sort_by(|a, b| self[b].cmp(&self[a]));
+            let (keep, rest) = variants.split_first().unwrap();
+            for path in rest {
+                let dir = self.remove(path).unwrap();
+                let kept = self.get_mut(keep).unwrap();
+                kept.rank += dir.rank;
+                kept.last_accessed = kept.last_accessed.max(dir.last_accessed);
+                kept.pinned |= dir.pinned;
+                if dir.origin == Origin::Visited {
+                    kept.origin = Origin::Visited;
+                }
+                merged += 1;
+            }
+        }
+        merged
+    }
+
+    /// A fully owned copy of every entry.
+    pub fn snapshot(&self) -> DirList<'static> {
+        DirList(
+            self.iter()
+                .map(|(key, dir)| (key.clone(), dir.clone().into_owned()))
+                .collect(),
+        )
+    }
+
+    /// Fold `other` in. A path present in both keeps the higher rank and
+    /// the most recent access time; other paths are copied over.
+    pub fn merge(&mut self, other: &DirList) {
+        for (key, dir) in other.iter() {
+            match self.entry(key.clone()) {
+                Entry::Occupied(mut e) => {
+                    let existing = e.get_mut();
+                    existing.rank = existing.rank.max(dir.rank);
+                    existing.last_accessed = existing.last_accessed.max(dir.last_accessed);
+                }
+                Entry::Vacant(e) => {
+                    e.insert(dir.clone().into_owned());
+                }
+            }
+        }
+    }
+
+    /// Move the entry at `from` to `to`, keeping its rank and access time.
+    /// An entry already at `to` is combined as in [`DirList::merge`].
+    /// Returns whether `from` was tracked.
+    pub fn rename(&mut self, from: &str, to: &str) -> bool {
+        let Some(dir) = self.remove(from) else {
+            return false;
+        };
+        let moved = Dir {
+            path: Cow::Owned(to.to_string()),
+            ..dir
+        };
+        self.merge(&DirList::from([(to.to_string(), moved)]));
+        true
+    }
+
+    /// Compare against `newer`. Ranks are compared at the datafile's
+    /// one-decimal precision so a saved copy matches its in-memory source.
+    pub fn diff(&self, newer: &DirList) -> DirListDiff {
+        let same_rank = |a: Ranking, b: Ranking| (a * 10.0).round() == (b * 10.0).round();
+        DirListDiff {
+            added: newer
+                .keys()
+                .filter(|k| !self.contains_key(*k))
+                .cloned()
+                .sorted()
+                .collect(),
+            removed: self
+                .keys()
+                .filter(|k| !newer.contains_key(*k))
+                .cloned()
+                .sorted()
+                .collect(),
+            changed: self
+                .iter()
+                .filter_map(|(k, old)| {
+                    let new = newer.get(k)?;
+                    (!same_rank(old.rank, new.rank)).then(|| (k.clone(), old.rank, new.rank))
+                })
+                .sorted_by(|a, b| a.0.cmp(&b.0))
+                .collect(),
+        }
+    }
+
+    /// Rank matching directories: primary key is the bucketed fuzzy score,
+    /// frecency breaks ties. Returned `Dir.rank` carries the frecency value
+    /// so callers can display the effective score.
+    pub fn query_with(&self, pattern: &str, opts: &QueryOptions) -> Vec<Dir<'_>> {
+        self.ranked(&[pattern], opts)
+            .into_iter()
+            .map(|(_, dir)| dir)
+            .collect()
+    }
+
+    /// [`DirList::query_with`], keeping each entry's fuzzy score and the
+    /// matched character positions.
+    pub fn query_scored(&self, pattern: &str, opts: &QueryOptions) -> Vec<ScoredDir<'_>> {
+        self.query_terms(&[pattern], opts)
+    }
+
+    /// [`DirList::query_scored`] for several terms that must all match
+    /// (AND). The score is the sum of the per-term scores and positions
+    /// are the union of each term's matches.
+    ///
+    /// With [`QueryOptions::exact_path`], a single term naming a stored
+    /// path returns that entry alone, ahead of any fuzzy match.
+    pub fn query_terms(&self, terms: &[&str], opts: &QueryOptions) -> Vec<ScoredDir<'_>> {
+        if let Some(exact) = match terms {
+            [term] if opts.exact_path => self.exact_match(term, opts),
+            _ => None,
+        } {
+            return vec![exact];
+        }
+        self.ranked(terms, opts)
+            .into_iter()
+            .map(|(score, dir)| ScoredDir {
+                visits: self
+                    .get(dir.path.as_ref())
+                    .map_or(0.0, |stored| stored.rank),
+                positions: terms
+                    .iter()
+                    .filter_map(|term| opts.matcher.match_positions(term, &dir.path))
+                    .flatten()
+                    .sorted()
+                    .dedup()
+                    .collect(),
+                dir,
+                score,
+            })
+            .collect()
+    }
+
+    /// The candidate stored at `path`, ignoring trailing slashes, scored as
+    /// a full match.
+    fn exact_match(&self, path: &str, opts: &QueryOptions) -> Option<ScoredDir<'_>> {
+        let trimmed = path.trim_end_matches('/');
+        let key = if trimmed.is_empty() { path } else { trimmed };
+        let dir = self.get(key).filter(|dir| is_candidate(dir, opts))?;
+        let visits = dir.rank;
+        let mut dir = dir.clone();
+        dir.rank = opts.effective_rank(&dir, now());
+        Some(ScoredDir {
+            visits,
+            positions: (0..dir.path.chars().count()).collect(),
+            dir,
+            score: SCORE_MAX,
+        })
+    }
+
+    /// All existing entries ordered by frecency. Returned `Dir.rank`
+    /// carries the frecency value.
+    pub fn list_with(&self, opts: &QueryOptions) -> Vec<Dir<'_>> {
+        let now = now();
+        self.values()
+            .filter(|dir| is_candidate(dir, opts))
+            .map(|dir| {
+                let mut dir = dir.clone();
+                dir.rank = opts.effective_rank(&dir, now);
+                dir
+            })
+            .sorted_by(|a, b| b.rank.total_cmp(&a.rank).then_with(|| opts.tie_break(a, b)))
+            .collect()
+    }
+
+    /// The `limit` most recently visited candidates, newest first, whatever
+    /// their rank. Equal visit times fall back to path order.
+    pub fn recent(&self, limit: usize, opts: &QueryOptions) -> Vec<Dir<'_>> {
+        self.values()
+            .filter(|dir| is_candidate(dir, opts))
+            .sorted_by(|a, b| {
+                b.last_accessed
+                    .cmp(&a.last_accessed)
+                    .then_with(|| a.path.cmp(&b.path))
+            })
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    /// One candidate drawn with probability proportional to its frecency,
+    /// reproducible for a given `seed`. Returned `Dir.rank` carries the
+    /// frecency value, as in [`DirList::list_with`].
+    pub fn random(&self, seed: u64, opts: &QueryOptions) -> Option<Dir<'_>> {
+        let dirs = self.list_with(opts);
+        let weights: Vec<f64> = dirs.iter().map(|dir| dir.rank).collect();
+        weighted_index(&weights, seed).map(|i| dirs[i].clone())
+    }
+
+    pub fn stats(&self) -> DbStats {
+        if self.is_empty() {
+            return DbStats::default();
+        }
+        let ranks = || self.values().map(|dir| dir.rank);
+        let total_visits: Ranking = ranks().sum();
+        DbStats {
+            entries: self.len(),
+            total_visits,
+            min_rank: ranks().fold(Ranking::INFINITY, Ranking::min),
+            max_rank: ranks().fold(Ranking::NEG_INFINITY, Ranking::max),
+            mean_rank: total_visits / self.len() as Ranking,
+            oldest: self.values().map(|dir| dir.last_accessed).min(),
+            newest: self.values().map(|dir| dir.last_accessed).max(),
+            missing: self.missing().len(),
+        }
+    }
+
+    /// Paths of entries whose directory no longer exists, sorted.
+    pub fn missing(&self) -> Vec<String> {
+        self.values()
+            .filter(|dir| !Path::new(dir.path.as_ref()).exists())
+            .map(|dir| dir.path.to_string())
+            .sorted()
+            .collect()
+    }
+
+    /// Paths matching the glob `pattern` (see [`glob_match`]), sorted.
+    pub fn matching_glob(&self, pattern: &str) -> Vec<String> {
+        self.keys()
+            .filter(|path| glob_match(pattern, path))
+            .cloned()
+            .sorted()
+            .collect()
+    }
+
+    /// Roll candidates up by their first `depth` path components, highest
+    /// aggregate rank first. Paths shorter than `depth` form their own group.
+    pub fn group_by(&self, depth: usize, opts: &QueryOptions) -> Vec<DirGroup> {
+        let mut groups: HashMap<String, DirGroup> = HashMap::new();
+        for dir in self.values().filter(|dir| is_candidate(dir, opts)) {
+            let components = dir.path.split('/').filter(|c| !c.is_empty()).take(depth);
+            let mut prefix = String::new();
+            for component in components {
+                prefix.push('/');
+                prefix.push_str(component);
+            }
+            let group = groups.entry(prefix.clone()).or_insert(DirGroup {
+                prefix,
+                count: 0,
+                rank: 0.0,
+            });
+            group.count += 1;
+            group.rank += dir.rank;
+        }
+        groups
+            .into_values()
+            .sorted_by(|a, b| {
+                b.rank
+                    .total_cmp(&a.rank)
+                    .then_with(|| a.prefix.cmp(&b.prefix))
+            })
+            .collect()
+    }
+
+    /// Matching entries with their raw fuzzy score, in query order.
+    fn ranked(&self, terms: &[&str], opts: &QueryOptions) -> Vec<(f64, Dir<'_>)> {
+        let now = now();
+        let candidates: Vec<&Dir> = self
+            .values()
+            .filter(|dir| is_candidate(dir, opts))
+            .collect();
+        let paths: Vec<&str> = candidates.iter().map(|dir| dir.path.as_ref()).collect();
+        let basenames: Vec<&str> = paths
+            .iter()
+            .map(|path| path.rsplit('/').next().unwrap_or(path))
+            .collect();
+        let mut scores = vec![0.0; candidates.len()];
+        for term in terms {
+            for (score, term_score) in scores.iter_mut().zip(opts.matcher.score_many(term, &paths))
+            {
+                *score += term_score;
+            }
+            if opts.basename_boost > 0.0 {
+                let base_scores = opts.matcher.score_many(term, &basenames);
+                for (score, base_score) in scores.iter_mut().zip(base_scores) {
+                    if base_score > crate::fuzzy::SCORE_MIN {
+                        *score += opts.basename_boost * base_score;
+                    }
+                }
+            }
+        }
+        candidates
+            .into_iter()
+            .zip(scores)
+            // A term that missed leaves -inf (or NaN next to an exact
+            // +inf match), which both fail this comparison.
+            .filter(|&(_, score)| score > crate::fuzzy::SCORE_MIN)
+            .map(|(dir, score)| {
+                let score = score - opts.depth_penalty_for(&dir.path);
+                let mut dir = dir.clone();
+                dir.rank = opts.effective_rank(&dir, now);
+                (score, dir)
+            })
+            .sorted_by(|a, b| {
+                score_bucket(b.0)
+                    .total_cmp(&score_bucket(a.0))
+                    .then_with(|| b.1.rank.total_cmp(&a.1.rank))
+                    .then_with(|| opts.tie_break(&a.1, &b.1))
+            })
+            .collect()
+    }
+
+    /// Scale every unpinned rank by `factor`. Unlike [`DirList::age`]
+    /// nothing is dropped, so a long break cools entries without forgetting
+    /// them.
+    pub fn decay(&mut self, factor: f64) {
+        for dir in self.values_mut().filter(|dir| !dir.pinned) {
+            dir.rank *= factor;
+        }
+    }
+
     /// Decay all ranks once their sum exceeds `max_total_rank`, dropping
     /// entries whose rank becomes negligible. Keeps ranks bounded over time
-    /// while preserving their relative order.
+    /// while preserving their relative order. Pinned entries neither count
+    /// towards the sum nor age.
     pub fn age(&mut self, max_total_rank: f64) {
-        let total: f64 = self.values().map(|d| d.rank).sum();
+        let total: f64 = self.values().filter(|d| !d.pinned).map(|d| d.rank).sum();
         if total <= max_total_rank {
             return;
         }
         self.retain(|_, dir| {
+            if dir.pinned {
+                return true;
+            }
             dir.rank *= AGE_DECAY;
             dir.rank >= AGE_DROP_THRESHOLD
         });
@@ -120,6 +597,87 @@ impl DerefMut for DirList<'_> {
     }
 }
 
+/// A query result with the metadata the matcher computed for it.
+#[derive(Debug, Clone)]
+pub struct ScoredDir<'a> {
+    /// The entry; `rank` carries the frecency value, as in query results.
+    pub dir: Dir<'a>,
+    /// Raw fuzzy score (see [`crate::fuzzy::match_score`]).
+    pub score: f64,
+    /// Matched character indices into `dir.path`.
+    pub positions: Vec<usize>,
+    /// The entry's stored rank, i.e. its visits, kept even when `dir` is
+    /// later shown under another path.
+    pub visits: Ranking,
+}
+
+/// Knobs for [`DirList::query_with`]. The default reproduces
+/// [`OpsDelegate::query`].
+#[derive(Debug, Clone, Default)]
+pub struct QueryOptions {
+    pub matcher: Matcher,
+    /// Skip entries whose stored rank (accumulated visits) is below this.
+    pub min_rank: Ranking,
+    /// Skip entries last visited before this epoch.
+    pub accessed_since: Epoch,
+    /// Only keep entries of this origin.
+    pub origin: Option<Origin>,
+    /// Skip entries whose path contains any of these substrings.
+    pub exclude: Vec<String>,
+    /// Skip entries inside any of these directories.
+    pub hidden: Vec<String>,
+    /// `(path prefix, multiplier)` pairs applied to frecency; the longest
+    /// matching prefix wins.
+    pub weights: Vec<(String, f64)>,
+    /// Trust stored paths instead of checking that each still exists.
+    pub skip_existence_check: bool,
+    /// Order entries that tie on score by path instead of leaving them in
+    /// hash order.
+    pub stable_order: bool,
+    /// Weight of each term's score against the last path component, added
+    /// to the full-path score. Zero disables the basename pass.
+    pub basename_boost: f64,
+    /// Score subtracted per path component beyond
+    /// [`DEPTH_PENALTY_FREE`], so many slash bonuses do not carry deeply
+    /// nested paths past shallow ones. Zero disables it.
+    pub depth_penalty: f64,
+    /// Let a single term that is a stored path match only that path.
+    pub exact_path: bool,
+}
+
+impl QueryOptions {
+    /// Frecency scaled by the weight of the longest configured prefix
+    /// containing `dir`; this is the rank shown and sorted on.
+    fn effective_rank(&self, dir: &Dir, now: Epoch) -> Ranking {
+        let weight = self
+            .weights
+            .iter()
+            .filter(|(prefix, _)| is_under(&dir.path, prefix))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map_or(1.0, |&(_, weight)| weight);
+        frecency(dir.rank, now, dir.last_accessed) * weight
+    }
+
+    /// Amount [`QueryOptions::depth_penalty`] takes off the fuzzy score of
+    /// `path`.
+    fn depth_penalty_for(&self, path: &str) -> f64 {
+        if self.depth_penalty <= 0.0 {
+            return 0.0;
+        }
+        let depth = path.split('/').filter(|c| !c.is_empty()).count();
+        depth.saturating_sub(DEPTH_PENALTY_FREE) as f64 * self.depth_penalty
+    }
+
+    /// Tie-breaker applied after the score comparison.
+    fn tie_break(&self, a: &Dir, b: &Dir) -> Ordering {
+        if self.stable_order {
+            a.path.cmp(&b.path)
+        } else {
+            Ordering::Equal
+        }
+    }
+}
+
 pub trait OpsDelegate {
     fn insert_or_update(&mut self, p: Cow<str>);
     fn delete<P: AsRef<str>>(&mut self, p: P);
@@ -128,79 +686,123 @@ pub trait OpsDelegate {
     fn clear_data(&mut self);
 }
 
+/// Current time as a datafile epoch.
 #[inline]
-fn now() -> Epoch {
+pub fn now() -> Epoch {
     SystemTime::now()
         .duration_since(SystemTime::UNIX_EPOCH)
         .expect("system clock before UNIX epoch")
         .as_secs()
 }
 
+/// Case-insensitive form of `path`, for `fold_case` comparisons.
+pub fn case_key(path: &str) -> String {
+    path.to_lowercase()
+}
+
+/// Whether `path` is `prefix` or inside it, matching whole components so
+/// `/a/bc` is not under `/a/b`.
+pub fn is_under(path: &str, prefix: &str) -> bool {
+    path.strip_prefix(prefix)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+/// Whether `path` matches the shell-style `pattern` as a whole: `?` is one
+/// character and `*` any run of characters within a path component, while
+/// `**` also crosses `/`.
+///
+/// Runs in `O(pattern × path)`: one pass per pattern token tracks which
+/// path prefixes can have been matched so far, so no backtracking.
+pub fn glob_match(pattern: &str, path: &str) -> bool {
+    let path: Vec<char> = path.chars().collect();
+    // matched[i]: the tokens so far can match exactly `path[..i]`.
+    let mut matched = vec![false; path.len() + 1];
+    matched[0] = true;
+    let mut tokens = pattern.chars().peekable();
+    while let Some(token) = tokens.next() {
+        let mut next = vec![false; path.len() + 1];
+        match token {
+            '*' if tokens.next_if_eq(&'*').is_some() => {
+                for i in 0..=path.len() {
+                    next[i] = matched[i] || (i > 0 && next[i - 1]);
+                }
+            }
+            '*' => {
+                for i in 0..=path.len() {
+                    next[i] = matched[i] || (i > 0 && next[i - 1] && path[i - 1] != '/');
+                }
+            }
+            '?' => {
+                for i in 0..path.len() {
+                    next[i + 1] = matched[i] && path[i] != '/';
+                }
+            }
+            c => {
+                for i in 0..path.len() {
+                    next[i + 1] = matched[i] && path[i] == c;
+                }
+            }
+        }
+        matched = next;
+    }
+    matched[path.len()]
+}
+
+/// Whether `dir` is eligible for query/list output under `opts`.
+fn is_candidate(dir: &Dir, opts: &QueryOptions) -> bool {
+    dir.rank >= opts.min_rank
+        && dir.last_accessed >= opts.accessed_since
+        && opts.origin.is_none_or(|origin| dir.origin == origin)
+        && !opts.exclude.iter().any(|p| dir.path.contains(p.as_str()))
+        && !opts.hidden.iter().any(|p| is_under(&dir.path, p))
+        && (opts.skip_existence_check || Path::new(dir.path.as_ref()).exists())
+}
+
 /// Bucket a fuzzy score to one decimal so that near-equal match qualities
 /// tie and let frecency decide the order.
-fn score_bucket(score: f64) -> f64 {
+pub fn score_bucket(score: f64) -> f64 {
     (score * 10.0).round()
 }
 
+/// An index into `weights` drawn with probability proportional to its
+/// weight, using one SplitMix64 output for `seed`. Weights that are not
+/// positive are never drawn; `None` when no weight is positive.
+pub fn weighted_index(weights: &[f64], seed: u64) -> Option<usize> {
+    let total: f64 = weights.iter().filter(|&&w| w > 0.0).sum();
+    if total <= 0.0 {
+        return None;
+    }
+    let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^= z >> 31;
+    // The top 53 bits give a uniform float in [0, 1).
+    let mut target = (z >> 11) as f64 / (1u64 << 53) as f64 * total;
+    for (i, &w) in weights.iter().enumerate().filter(|(_, &w)| w > 0.0) {
+        if target < w {
+            return Some(i);
+        }
+        target -= w;
+    }
+    // Rounding can leave a sliver past the last weight.
+    weights.iter().rposition(|&w| w > 0.0)
+}
+
 impl OpsDelegate for DirList<'_> {
     fn insert_or_update(&mut self, p: Cow<'_, str>) {
-        let key = p.to_string();
-        let now = now();
-        match self.entry(key) {
-            Entry::Vacant(e) => {
-                e.insert(Dir {
-                    path: Cow::Owned(p.into()),
-                    rank: 1.0,
-                    last_accessed: now,
-                });
-            }
-            Entry::Occupied(mut e) => {
-                let dir = e.get_mut();
-                dir.rank += 1.0;
-                dir.last_accessed = now;
-            }
-        }
+        self.insert_at(p, now());
     }
 
     fn delete<P: AsRef<str>>(&mut self, path: P) {
         self.remove(path.as_ref());
     }
 
-    /// Rank matching directories: primary key is the bucketed fuzzy score,
-    /// frecency breaks ties. Returned `Dir.rank` carries the frecency value
-    /// so callers can display the effective score.
     fn query<S: AsRef<str>>(&self, pattern: S) -> Vec<Dir<'_>> {
-        let pattern = pattern.as_ref();
-        let now = now();
-        self.values()
-            .filter(|dir| Path::new(dir.path.as_ref()).exists())
-            .filter_map(|dir| {
-                let score = crate::fuzzy::match_score(pattern, &dir.path);
-                (score > crate::fuzzy::SCORE_MIN).then(|| {
-                    let mut dir = dir.clone();
-                    dir.rank = frecency(dir.rank, now, dir.last_accessed);
-                    (score_bucket(score), dir)
-                })
-            })
-            .sorted_by(|a, b| {
-                b.0.total_cmp(&a.0)
-                    .then_with(|| b.1.rank.total_cmp(&a.1.rank))
-            })
-            .map(|(_, dir)| dir)
-            .collect()
+        self.query_with(pattern.as_ref(), &QueryOptions::default())
     }
 
     fn list(&self) -> Vec<Dir<'_>> {
-        let now = now();
-        self.values()
-            .filter(|dir| Path::new(dir.path.as_ref()).exists())
-            .map(|dir| {
-                let mut dir = dir.clone();
-                dir.rank = frecency(dir.rank, now, dir.last_accessed);
-                dir
-            })
-            .sorted_by(|a, b| b.rank.total_cmp(&a.rank))
-            .collect()
+        self.list_with(&QueryOptions::default())
     }
 
     fn clear_data(&mut self) {
@@ -252,11 +854,36 @@ mod test_dir_list {
     use super::*;
 
     fn dir(path: &str, rank: f64, last_accessed: Epoch) -> Dir<'static> {
-        Dir {
-            path: Cow::Owned(path.to_string()),
-            rank,
-            last_accessed,
+        Dir::new(path.to_string())
+            .with_rank(rank)
+            .with_last_accessed(last_accessed)
+    }
+
+    #[test]
+    fn weighted_index_is_seeded_and_skips_zero_weights() {
+        let weights = [0.0, 3.0, 0.0, 1.0, -2.0];
+        for seed in 0..1000 {
+            let picked = weighted_index(&weights, seed).unwrap();
+            assert!(picked == 1 || picked == 3, "seed {seed} picked {picked}");
+            assert_eq!(weighted_index(&weights, seed), Some(picked));
         }
+        let ones = (0..1000)
+            .filter(|&seed| weighted_index(&weights, seed) == Some(1))
+            .count();
+        assert!((650..850).contains(&ones), "rank 3 of 4 drawn {ones}/1000");
+        assert_eq!(weighted_index(&[0.0, 0.0], 7), None);
+        assert_eq!(weighted_index(&[], 7), None);
+    }
+
+    #[test]
+    fn builder_defaults_to_a_single_unstamped_visit() {
+        let d = Dir::new("/tmp");
+        assert_eq!(d.path, "/tmp");
+        assert_eq!(d.rank, 1.0);
+        assert_eq!(d.last_accessed, 0);
+
+        let d = d.with_rank(4.5).with_last_accessed(100);
+        assert_eq!((d.rank, d.last_accessed), (4.5, 100));
     }
 
     #[test]
@@ -295,6 +922,21 @@ mod test_dir_list {
         );
     }
 
+    #[test]
+    fn pinned_entries_survive_aging_and_decay() {
+        let mut list = DirList::new();
+        list.insert("/hot".to_string(), dir("/hot", 90.0, 0));
+        let mut pinned = dir("/pinned", 1.0, 0);
+        pinned.pinned = true;
+        list.insert("/pinned".to_string(), pinned);
+        list.insert("/cold".to_string(), dir("/cold", 1.0, 0));
+        list.age(50.0);
+        list.decay(0.5);
+        assert_eq!(list.get("/pinned").unwrap().rank, 1.0);
+        assert!(!list.contains_key("/cold"));
+        assert!((list.get("/hot").unwrap().rank - 40.5).abs() < 1e-9);
+    }
+
     #[test]
     fn dir_eq_is_consistent_with_ord() {
         let a = dir("/a", 1.0, 100);
@@ -304,6 +946,34 @@ mod test_dir_list {
         assert_eq!(a.cmp(&a.clone()), Ordering::Equal);
     }
 
+    #[test]
+    fn touch_bumps_rank_and_access_time() {
+        let mut d = dir("/a", 2.5, 100);
+        d.touch(500);
+        assert!((d.rank - 3.5).abs() < 1e-9);
+        assert_eq!(d.last_accessed, 500);
+        assert_eq!(d.path, "/a");
+    }
+
+    #[test]
+    fn diff_classifies_added_removed_and_changed() {
+        let old = DirList::from([
+            ("/gone".to_string(), dir("/gone", 1.0, 0)),
+            ("/same".to_string(), dir("/same", 2.0, 0)),
+            ("/bumped".to_string(), dir("/bumped", 2.0, 0)),
+        ]);
+        let new = DirList::from([
+            ("/same".to_string(), dir("/same", 2.04, 0)),
+            ("/bumped".to_string(), dir("/bumped", 3.0, 0)),
+            ("/fresh".to_string(), dir("/fresh", 1.0, 0)),
+        ]);
+        let diff = old.diff(&new);
+        assert_eq!(diff.added, vec!["/fresh"]);
+        assert_eq!(diff.removed, vec!["/gone"]);
+        assert_eq!(diff.changed, vec![("/bumped".to_string(), 2.0, 3.0)]);
+        assert!(old.diff(&old).is_empty());
+    }
+
     #[test]
     fn delete_removes_entry() {
         let mut list = DirList::new();
@@ -322,14 +992,49 @@ mod test_query {
         let p = path.to_str().unwrap().to_string();
         list.insert(
             p.clone(),
-            Dir {
-                path: Cow::Owned(p),
-                rank,
-                last_accessed: last,
-            },
+            Dir::new(p).with_rank(rank).with_last_accessed(last),
         );
     }
 
+    #[test]
+    fn recent_orders_by_last_access_and_ignores_rank() {
+        let tmp = tempdir().unwrap();
+        let mut list = DirList::new();
+        for (name, rank, last) in [("old-fav", 50.0, 100), ("new", 1.0, 300), ("mid", 2.0, 200)] {
+            let path = tmp.path().join(name);
+            std::fs::create_dir(&path).unwrap();
+            insert(&mut list, &path, rank, last);
+        }
+        insert(&mut list, &tmp.path().join("gone"), 1.0, 400);
+
+        let recent = list.recent(10, &QueryOptions::default());
+        let times: Vec<Epoch> = recent.iter().map(|dir| dir.last_accessed).collect();
+        assert_eq!(times, vec![300, 200, 100], "missing paths are skipped");
+        assert!(recent[0].path.ends_with("new"));
+        assert_eq!(list.recent(2, &QueryOptions::default()).len(), 2);
+    }
+
+    #[test]
+    fn random_pick_is_reproducible_and_never_zero_rank() {
+        let tmp = tempdir().unwrap();
+        let mut list = DirList::new();
+        let now = now();
+        for (name, rank) in [("never", 0.0), ("often", 9.0), ("rarely", 1.0)] {
+            let path = tmp.path().join(name);
+            std::fs::create_dir(&path).unwrap();
+            insert(&mut list, &path, rank, now);
+        }
+        let opts = QueryOptions::default();
+
+        let first = list.random(42, &opts).unwrap().path.into_owned();
+        assert_eq!(list.random(42, &opts).unwrap().path, first);
+        for seed in 0..200 {
+            let picked = list.random(seed, &opts).unwrap();
+            assert!(!picked.path.ends_with("never"), "seed {seed}");
+        }
+        assert!(DirList::new().random(42, &opts).is_none());
+    }
+
     #[test]
     fn query_finds_dir_despite_transposed_characters() {
         let tmp = tempdir().unwrap();
@@ -353,6 +1058,12 @@ mod test_query {
             now(),
         );
         assert!(list.query("real").is_empty());
+
+        let opts = QueryOptions {
+            skip_existence_check: true,
+            ..QueryOptions::default()
+        };
+        assert_eq!(list.query_with("real", &opts).len(), 1);
     }
 
     #[test]
@@ -375,6 +1086,325 @@ mod test_query {
         );
     }
 
+    #[test]
+    fn min_rank_filters_low_visit_entries() {
+        let tmp = tempdir().unwrap();
+        let mut list = DirList::new();
+        for (name, rank) in [("once", 1.0), ("few", 3.0), ("often", 8.0)] {
+            let dir = tmp.path().join(name);
+            std::fs::create_dir(&dir).unwrap();
+            insert(&mut list, &dir, rank, now());
+        }
+        let opts = QueryOptions {
+            min_rank: 3.0,
+            ..QueryOptions::default()
+        };
+        let names = |dirs: Vec<Dir>| -> Vec<String> {
+            dirs.iter()
+                .map(|d| d.path.rsplit('/').next().unwrap().to_string())
+                .collect()
+        };
+        assert_eq!(names(list.list_with(&opts)), ["often", "few"]);
+        let matched = names(list.query_with("often", &opts));
+        assert_eq!(matched[0], "often");
+        assert!(!matched.contains(&"once".to_string()));
+        assert_eq!(list.list().len(), 3, "no threshold by default");
+    }
+
+    #[test]
+    fn group_by_rolls_up_subdirectories() {
+        let tmp = tempdir().unwrap();
+        let mut list = DirList::new();
+        for (sub, rank) in [
+            ("work/api", 4.0),
+            ("work/web", 2.0),
+            ("work", 1.0),
+            ("play/game", 5.0),
+        ] {
+            let dir = tmp.path().join(sub);
+            std::fs::create_dir_all(&dir).unwrap();
+            insert(&mut list, &dir, rank, now());
+        }
+        let depth = tmp.path().components().count();
+        let root = tmp.path().to_str().unwrap();
+
+        let groups = list.group_by(depth, &QueryOptions::default());
+        assert_eq!(
+            groups,
+            [
+                DirGroup {
+                    prefix: format!("{root}/work"),
+                    count: 3,
+                    rank: 7.0,
+                },
+                DirGroup {
+                    prefix: format!("{root}/play"),
+                    count: 1,
+                    rank: 5.0,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn every_term_must_match() {
+        // Fixed paths: letters in a random temp dir name could fuzzily
+        // match a term and let an extra path through.
+        let both = std::path::Path::new("/srv/kiwi/mango");
+        let kiwi_only = std::path::Path::new("/srv/kiwi/grape");
+        let mango_only = std::path::Path::new("/srv/mango");
+        let mut list = DirList::new();
+        for (dir, rank) in [(both, 1.0), (kiwi_only, 9.0), (mango_only, 9.0)] {
+            insert(&mut list, dir, rank, now());
+        }
+        let opts = QueryOptions {
+            skip_existence_check: true,
+            ..QueryOptions::default()
+        };
+
+        let hits = list.query_terms(&["kiwi", "mango"], &opts);
+        assert_eq!(hits.len(), 1, "paths matching only one term are excluded");
+        assert_eq!(hits[0].dir.path, both.to_str().unwrap());
+        let path: Vec<char> = hits[0].dir.path.chars().collect();
+        let matched: String = hits[0].positions.iter().map(|&i| path[i]).collect();
+        assert_eq!(matched, "kiwimango");
+
+        let single = list.query_terms(&["mango"], &opts);
+        assert_eq!(single.len(), 2, "one term behaves like query_scored");
+    }
+
+    #[test]
+    fn stable_order_is_independent_of_insertion_order() {
+        let tmp = tempdir().unwrap();
+        let names = ["pear", "apple", "plum", "peach", "fig"];
+        let opts = QueryOptions {
+            stable_order: true,
+            ..QueryOptions::default()
+        };
+        let ordered = |names: &[&str]| {
+            let mut list = DirList::new();
+            for name in names {
+                let dir = tmp.path().join(name);
+                std::fs::create_dir_all(&dir).unwrap();
+                insert(&mut list, &dir, 2.0, 1000);
+            }
+            let listed: Vec<String> = list
+                .list_with(&opts)
+                .iter()
+                .map(|d| d.to_string())
+                .collect();
+            let queried: Vec<String> = list
+                .query_with("p", &opts)
+                .iter()
+                .map(|d| d.to_string())
+                .collect();
+            (listed, queried)
+        };
+
+        let first = ordered(&names);
+        let mut shuffled = names;
+        shuffled.reverse();
+        shuffled.swap(1, 3);
+        assert_eq!(first, ordered(&shuffled));
+        assert!(first.0.is_sorted(), "equal ranks fall back to path order");
+    }
+
+    #[test]
+    fn stats_summarize_the_list() {
+        let tmp = tempdir().unwrap();
+        let mut list = DirList::new();
+        assert_eq!(list.stats(), DbStats::default());
+
+        for (name, rank, last) in [("a", 1.0, 300), ("b", 2.0, 100), ("c", 6.0, 200)] {
+            let dir = tmp.path().join(name);
+            std::fs::create_dir(&dir).unwrap();
+            insert(&mut list, &dir, rank, last);
+        }
+        insert(&mut list, &tmp.path().join("gone"), 3.0, 250);
+
+        assert_eq!(
+            list.stats(),
+            DbStats {
+                entries: 4,
+                total_visits: 12.0,
+                min_rank: 1.0,
+                max_rank: 6.0,
+                mean_rank: 3.0,
+                oldest: Some(100),
+                newest: Some(300),
+                missing: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn basename_boost_prefers_matches_in_the_last_component() {
+        let tmp = tempdir().unwrap();
+        let concentrated = tmp.path().join("a/b/myproj");
+        let scattered = tmp.path().join("myproj/a/b");
+        let mut list = DirList::new();
+        for dir in [&concentrated, &scattered] {
+            std::fs::create_dir_all(dir).unwrap();
+            insert(&mut list, dir, 1.0, 1000);
+        }
+        let opts = QueryOptions {
+            basename_boost: 0.5,
+            ..QueryOptions::default()
+        };
+        let hits = list.query_with("proj", &opts);
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].path, concentrated.to_str().unwrap());
+    }
+
+    #[test]
+    fn depth_penalty_lets_a_shallow_match_beat_a_deep_one() {
+        let tmp = tempdir().unwrap();
+        let shallow = tmp.path().join("proj");
+        let deep = tmp.path().join("a/b/c/d/e/proj");
+        let mut list = DirList::new();
+        std::fs::create_dir_all(&shallow).unwrap();
+        std::fs::create_dir_all(&deep).unwrap();
+        insert(&mut list, &shallow, 1.0, 1000);
+        insert(&mut list, &deep, 10.0, 1000);
+
+        let hits = list.query_with("proj", &QueryOptions::default());
+        assert_eq!(hits[0].path, deep.to_str().unwrap());
+
+        let opts = QueryOptions {
+            depth_penalty: 0.5,
+            ..QueryOptions::default()
+        };
+        let hits = list.query_with("proj", &opts);
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].path, shallow.to_str().unwrap());
+    }
+
+    #[test]
+    fn exact_path_query_beats_a_higher_ranked_near_match() {
+        let tmp = tempdir().unwrap();
+        let proj = tmp.path().join("proj");
+        let deep = proj.join("deep");
+        std::fs::create_dir_all(&deep).unwrap();
+        let mut list = DirList::new();
+        insert(&mut list, &proj, 1.0, 0);
+        insert(&mut list, &deep, 50.0, now());
+        let term = format!("{}/", proj.display());
+
+        let opts = QueryOptions {
+            exact_path: true,
+            ..QueryOptions::default()
+        };
+        let hits = list.query_terms(&[&term], &opts);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(Path::new(hits[0].dir.path.as_ref()), proj);
+
+        let fuzzy = list.query_terms(&[&term], &QueryOptions::default());
+        assert_eq!(Path::new(fuzzy[0].dir.path.as_ref()), deep);
+    }
+
+    #[test]
+    fn accessed_since_drops_older_entries() {
+        let tmp = tempdir().unwrap();
+        let boot = 1_700_000_000;
+        let mut list = DirList::new();
+        for (name, last) in [("before", boot - 60), ("at", boot), ("after", boot + 60)] {
+            let dir = tmp.path().join(name);
+            std::fs::create_dir(&dir).unwrap();
+            insert(&mut list, &dir, 1.0, last);
+        }
+        let opts = QueryOptions {
+            accessed_since: boot,
+            ..QueryOptions::default()
+        };
+        let names: Vec<_> = list
+            .list_with(&opts)
+            .iter()
+            .map(|d| d.path.rsplit('/').next().unwrap().to_string())
+            .sorted()
+            .collect();
+        assert_eq!(names, ["after", "at"]);
+    }
+
+    #[test]
+    fn exclude_drops_every_listed_pattern() {
+        let tmp = tempdir().unwrap();
+        let mut list = DirList::new();
+        for name in ["app/src", "app/node_modules/x", "app/target/debug"] {
+            let dir = tmp.path().join(name);
+            std::fs::create_dir_all(&dir).unwrap();
+            insert(&mut list, &dir, 1.0, now());
+        }
+        let opts = QueryOptions {
+            exclude: vec!["node_modules".to_string(), "/target".to_string()],
+            ..QueryOptions::default()
+        };
+        let kept: Vec<String> = list
+            .list_with(&opts)
+            .iter()
+            .map(|d| d.to_string())
+            .collect();
+        assert_eq!(kept, [tmp.path().join("app/src").to_str().unwrap()]);
+        assert_eq!(list.query_with("app", &opts).len(), 1);
+    }
+
+    #[test]
+    fn weighted_root_outranks_equal_frecency() {
+        let tmp = tempdir().unwrap();
+        let work = tmp.path().join("work/proj");
+        let play = tmp.path().join("play/proj");
+        let mut list = DirList::new();
+        for dir in [&work, &play] {
+            std::fs::create_dir_all(dir).unwrap();
+            insert(&mut list, dir, 3.0, 1000);
+        }
+        let weighted = |root: &std::path::Path| QueryOptions {
+            weights: vec![(root.to_str().unwrap().to_string(), 2.0)],
+            ..QueryOptions::default()
+        };
+
+        for root in ["work", "play"] {
+            let opts = weighted(&tmp.path().join(root));
+            let top = &list.query_with("proj", &opts)[0];
+            assert!(
+                top.path.contains(root),
+                "{root} should win, got {}",
+                top.path
+            );
+            assert!(list.list_with(&opts)[0].path.contains(root));
+        }
+        let partial = weighted(&tmp.path().join("wo"));
+        let ranks: Vec<f64> = list.list_with(&partial).iter().map(|d| d.rank).collect();
+        assert_eq!(ranks[0], ranks[1], "prefixes match whole components only");
+    }
+
+    #[test]
+    fn glob_star_stays_within_a_component() {
+        let list = DirList::from(
+            ["/s/a", "/s/b/c", "/s/ab", "/t/a"].map(|p| (p.to_string(), Dir::new(p))),
+        );
+        assert_eq!(list.matching_glob("/s/*"), ["/s/a", "/s/ab"]);
+        assert_eq!(list.matching_glob("/s/**"), ["/s/a", "/s/ab", "/s/b/c"]);
+        assert_eq!(list.matching_glob("/?/a"), ["/s/a", "/t/a"]);
+        assert_eq!(list.matching_glob("/s/a*"), ["/s/a", "/s/ab"]);
+        assert!(list.matching_glob("/s").is_empty());
+    }
+
+    #[test]
+    fn glob_match_handles_pathological_patterns_quickly() {
+        let path = format!("/{}", "a".repeat(60));
+        let pattern = format!("{}b", "**a".repeat(30));
+        let started = std::time::Instant::now();
+        assert!(!glob_match(&pattern, &path));
+        assert!(glob_match(&"*a".repeat(30), &"a".repeat(60)));
+        assert!(glob_match("/**/x/*.rs", "/src/deep/x/main.rs"));
+        assert!(!glob_match("/**/x/*.rs", "/src/x/deep/main.rs"));
+        assert!(
+            started.elapsed() < std::time::Duration::from_secs(1),
+            "backtracking blew up: {:?}",
+            started.elapsed()
+        );
+    }
+
     #[test]
     fn better_match_quality_beats_higher_frecency() {
         let tmp = tempdir().unwrap();
@@ -0,0 +1,162 @@
+//! Device and inode numbers of tracked directories, kept in a
+//! `<datafile>.inodes` sidecar as one `dev:ino:born|path` line per entry,
+//! so the z-compatible datafile itself stays unchanged. `born` is left out
+//! where the filesystem records no birth time, as in older sidecars.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+
+use super::data::write_file;
+
+/// Identity of a directory. Filesystems hand a freed inode number to the
+/// next new file, so device and inode alone may name a different
+/// directory later; the birth time tells them apart. Unlike ctime, it
+/// survives the rename being tracked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileId {
+    pub dev: u64,
+    pub ino: u64,
+    /// Creation time in nanoseconds since the epoch, where recorded.
+    pub born: Option<u64>,
+}
+
+pub type Inodes = BTreeMap<String, FileId>;
+
+pub fn load(path: &Path) -> Result<Inodes> {
+    if !path.exists() {
+        return Ok(Inodes::new());
+    }
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("failed to read inodes {}", path.display()))?;
+    let mut inodes = Inodes::new();
+    for (line_num, line) in text.lines().enumerate() {
+        if line.is_empty() {
+            continue;
+        }
+        let id = (|| {
+            let (id, target) = line.split_once('|')?;
+            let mut fields = id.split(':');
+            let dev = fields.next()?.parse().ok()?;
+            let ino = fields.next()?.parse().ok()?;
+            let born = match fields.next() {
+                Some(born) => Some(born.parse().ok()?),
+                None => None,
+            };
+            if fields.next().is_some() {
+                return None;
+            }
+            Some((target, FileId { dev, ino, born }))
+        })()
+        .ok_or_else(|| anyhow!("invalid inode entry at line {}: {}", line_num + 1, line))?;
+        inodes.insert(id.0.to_string(), id.1);
+    }
+    Ok(inodes)
+}
+
+pub fn save(path: &Path, inodes: &Inodes) -> Result<()> {
+    let mut buffer = String::new();
+    for (target, id) in inodes {
+        match id.born {
+            Some(born) => buffer.push_str(&format!("{}:{}:{}|{}\n", id.dev, id.ino, born, target)),
+            None => buffer.push_str(&format!("{}:{}|{}\n", id.dev, id.ino, target)),
+        }
+    }
+    write_file(path, buffer)
+}
+
+#[cfg(unix)]
+pub fn file_id(path: &Path) -> Option<FileId> {
+    use std::os::unix::fs::MetadataExt;
+    let meta = fs::metadata(path).ok()?;
+    let born = meta
+        .created()
+        .ok()
+        .and_then(|created| created.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|since_epoch| since_epoch.as_nanos() as u64);
+    Some(FileId {
+        dev: meta.dev(),
+        ino: meta.ino(),
+        born,
+    })
+}
+
+#[cfg(not(unix))]
+pub fn file_id(_path: &Path) -> Option<FileId> {
+    None
+}
+
+/// Where the directory once at `old` with `id` lives now. Only the old
+/// parent is searched, which covers the common in-place rename without
+/// walking the filesystem. A candidate must match device, inode and birth
+/// time; an entry recorded without a birth time only matches one whose
+/// filesystem records none either.
+pub fn find_renamed(old: &Path, id: FileId) -> Option<String> {
+    let parent = old.parent()?;
+    fs::read_dir(parent)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .find(|candidate| candidate.is_dir() && file_id(candidate) == Some(id))
+        .and_then(|found| found.to_str().map(str::to_string))
+}
+
+#[cfg(test)]
+mod test_inode {
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("zcddata.inodes");
+        let mut inodes = Inodes::new();
+        let id = |dev, ino, born| FileId { dev, ino, born };
+        inodes.insert(
+            "/home/u/work".into(),
+            id(66306, 1234, Some(1_700_000_000_123)),
+        );
+        inodes.insert("/odd|dir".into(), id(1, 2, None));
+        save(&path, &inodes).unwrap();
+        assert_eq!(load(&path).unwrap(), inodes);
+    }
+
+    #[test]
+    fn legacy_lines_load_without_a_birth_time() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("zcddata.inodes");
+        fs::write(&path, "66306:1234|/home/u/work\n").unwrap();
+        let inodes = load(&path).unwrap();
+        assert_eq!(
+            inodes["/home/u/work"],
+            FileId {
+                dev: 66306,
+                ino: 1234,
+                born: None
+            }
+        );
+        fs::write(&path, "1:2:3:4|/too/many\n").unwrap();
+        assert!(load(&path).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn a_reused_inode_with_another_birth_time_is_not_followed() {
+        let dir = tempfile::tempdir().unwrap();
+        let old = dir.path().join("old");
+        let new = dir.path().join("new");
+        fs::create_dir(&new).unwrap();
+        let id = file_id(&new).unwrap();
+        assert_eq!(find_renamed(&old, id).as_deref(), new.to_str());
+
+        let Some(born) = id.born else {
+            return; // no birth time on this filesystem to tell them apart
+        };
+        let reused = FileId {
+            born: Some(born + 1),
+            ..id
+        };
+        assert_eq!(find_renamed(&old, reused), None);
+    }
+}
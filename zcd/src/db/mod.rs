@@ -1,26 +1,55 @@
+mod bookmark;
 mod data;
 pub mod dir;
+mod inode;
+mod origin;
+mod pin;
+mod storage;
+pub mod usage;
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
+use itertools::Itertools;
 use std::borrow::Cow;
-use std::path::Path;
+use std::ffi::{OsStr, OsString};
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
 
-use data::{expand_path, open_file, write_file};
-pub use dir::{Dir, DirList, OpsDelegate};
+use data::{open_file, write_file};
+pub use data::{DataFormat, Delimiter, LineOrder, TsvColumns};
+use dir::Epoch;
+pub use dir::{
+    DbStats, Dir, DirGroup, DirList, DirListDiff, OpsDelegate, Origin, QueryOptions, ScoredDir,
+};
+pub use storage::{Storage, TextStorage};
 
-use crate::config::{load_config_from_path, Config};
+use crate::config::{home_dir, load_config_from_path, Config, ExcludeMode, TiePolicy};
+use crate::fuzzy::{Matcher, ScoreConfig, SCORE_MAX};
+use bookmark::{Bookmarks, BOOKMARK_PREFIX};
+use inode::Inodes;
+
+/// One window of ranked query results plus the total number of matches.
+#[derive(Debug)]
+pub struct QueryPage<'a> {
+    pub dirs: Vec<ScoredDir<'a>>,
+    pub total: usize,
+}
 
 pub struct Database<'a> {
     delegate: DirList<'a>,
     dirty: bool,
     config: Config,
+    storage: Box<dyn Storage>,
+    bookmarks: Bookmarks,
+    /// Filled only with `track_inode`.
+    inodes: Inodes,
+    /// The storage's save time and the idle decay factor applied to it
+    /// on load, so [`Database::verify`] can decay a re-read the same way.
+    load_decay: Option<(Epoch, f64)>,
 }
 
 impl OpsDelegate for Database<'_> {
     fn insert_or_update(&mut self, path: Cow<str>) {
-        self.delegate.insert_or_update(path);
-        self.delegate.age(self.config.max_age as f64);
-        self.dirty = true;
+        self.insert_at(path, dir::now());
     }
 
     fn delete<P: AsRef<str>>(&mut self, path: P) {
@@ -29,11 +58,12 @@ impl OpsDelegate for Database<'_> {
     }
 
     fn query<S: AsRef<str>>(&self, pattern: S) -> Vec<Dir<'_>> {
-        self.delegate.query(pattern)
+        self.delegate
+            .query_with(pattern.as_ref(), &self.query_options())
     }
 
     fn list(&self) -> Vec<Dir<'_>> {
-        self.delegate.list()
+        self.delegate.list_with(&self.query_options())
     }
 
     fn clear_data(&mut self) {
@@ -41,58 +71,610 @@ impl OpsDelegate for Database<'_> {
     }
 }
 
-fn load_datafile(p: &str) -> Result<DirList<'static>> {
-    let path = expand_path(p).context("failed to resolve datafile path")?;
-    if !path.exists() {
-        return Ok(DirList::new());
+/// Merge datafiles in `format` offline, with [`Database::import`]
+/// semantics, and write the result to `output`. No config or live
+/// database is involved. Returns the number of merged entries.
+pub fn merge_files(inputs: &[PathBuf], output: &Path, format: DataFormat) -> Result<usize> {
+    let mut merged = DirList::new();
+    for input in inputs {
+        let dirs = open_file(input)
+            .and_then(|file| format.from_bytes(file))
+            .with_context(|| format!("failed to read {}", input.display()))?;
+        merged.merge(&dirs);
+    }
+    write_file(output, format.to_bytes(&merged))
+        .with_context(|| format!("failed to write {}", output.display()))?;
+    Ok(merged.len())
+}
+
+/// Compare two datafiles in `format` offline: paths added in `new`,
+/// removed from `old`, and shared paths whose rank (visit count) changed.
+pub fn diff_files(old: &Path, new: &Path, format: DataFormat) -> Result<DirListDiff> {
+    let read = |path: &Path| {
+        open_file(path)
+            .and_then(|file| format.from_bytes(file))
+            .with_context(|| format!("failed to read {}", path.display()))
+    };
+    Ok(read(old)?.diff(&read(new)?))
+}
+
+/// Apply the sidecar marks (pins, imported entries) and `fold_case` to
+/// freshly loaded entries. Returns whether folding changed them.
+fn prepare_loaded(config: &Config, delegate: &mut DirList) -> Result<bool> {
+    for path in pin::load(&sidecar_path(&config.datafile, "pins"))? {
+        if let Some(dir) = delegate.get_mut(&path) {
+            dir.pinned = true;
+        }
+    }
+    for path in origin::load(&sidecar_path(&config.datafile, "imported"))? {
+        if let Some(dir) = delegate.get_mut(&path) {
+            dir.origin = Origin::Imported;
+        }
     }
-    let file = open_file(path.as_path()).context("failed to open datafile")?;
-    data::from_bytes(file).with_context(|| format!("failed to parse datafile {}", p))
+    Ok(config.fold_case && delegate.fold_case() > 0)
+}
+
+/// Rank multiplier for a datafile last saved at `saved_at`: `rate` of the
+/// rank is lost per idle day, compounding. A rate of 0 disables it.
+fn idle_decay(rate: f64, saved_at: Epoch, now: Epoch) -> f64 {
+    let idle_days = now.saturating_sub(saved_at) as f64 / 86_400.0;
+    (1.0 - rate).powf(idle_days)
+}
+
+/// Whether `path` exists but is not a directory. Symlinks are followed,
+/// and paths that do not exist (yet) are given the benefit of the doubt.
+fn is_non_dir(path: &Path) -> bool {
+    path.exists() && !path.is_dir()
+}
+
+fn warn_non_utf8(lossy: &str) {
+    eprintln!("zcd: skipping non-UTF-8 path: {:.64}", lossy.escape_debug());
+}
+
+/// Extensions of zcd-owned files kept next to the datafile. `clear --all`
+/// removes exactly these, plus quarantined `<datafile>.corrupt.<timestamp>`
+/// files, and never anything else in that directory.
+const SIDECAR_EXTENSIONS: &[&str] = &["bak", "corrupt", "bookmarks", "inodes", "pins", "imported"];
+
+/// Whether `err` comes from failing to read the data rather than from data
+/// that could not be parsed. Invalid UTF-8 surfaces as an `InvalidData` I/O
+/// error while parsing, so it counts as a parse failure.
+fn is_io_error(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        cause
+            .downcast_ref::<std::io::Error>()
+            .is_some_and(|io| io.kind() != std::io::ErrorKind::InvalidData)
+    })
+}
+
+/// Files `Storage::quarantine` moved aside: `<datafile>.corrupt.<suffix>`.
+fn quarantined_files(datafile: &Path) -> Vec<PathBuf> {
+    let (Some(dir), Some(name)) = (datafile.parent(), datafile.file_name()) else {
+        return Vec::new();
+    };
+    let mut prefix = name.to_os_string();
+    prefix.push(".corrupt.");
+    let prefix = prefix.as_encoded_bytes().to_vec();
+    let dir = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+    std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_name().as_encoded_bytes().starts_with(&prefix))
+        .map(|entry| entry.path())
+        .collect()
+}
+
+/// `<datafile>.<ext>`, keeping the datafile's own name intact.
+fn sidecar_path(datafile: &Path, ext: &str) -> PathBuf {
+    let mut name = OsString::from(datafile.as_os_str());
+    name.push(".");
+    name.push(ext);
+    PathBuf::from(name)
 }
 
 impl Database<'_> {
     pub fn new(config_path: &Path) -> Result<Self> {
         let config = load_config_from_path(config_path).context("failed to load config")?;
-        let delegate = load_datafile(&config.datafile).context("failed to load data")?;
+        Self::with_config(config)
+    }
+
+    pub fn with_config(config: Config) -> Result<Self> {
+        let storage = TextStorage::new(&config.datafile)
+            .with_format(config.datafile_format)
+            .with_order(config.datafile_sort);
+        Self::with_storage(config, Box::new(storage))
+    }
+
+    /// Back the database with `storage` instead of the configured datafile.
+    ///
+    /// Unparsable data does not make zcd unusable: it is quarantined and
+    /// the database starts empty, so the user can re-import. I/O errors,
+    /// and data the storage cannot quarantine, are returned instead.
+    pub fn with_storage(config: Config, mut storage: Box<dyn Storage>) -> Result<Self> {
+        let mut delegate = match storage.load() {
+            Ok(delegate) => delegate,
+            Err(err) if is_io_error(&err) => return Err(err.context("failed to load data")),
+            Err(err) => {
+                let moved = match storage.quarantine() {
+                    Ok(moved) => moved,
+                    Err(_) => return Err(err.context("failed to load data")),
+                };
+                eprintln!(
+                    "zcd: {:#}; starting empty, unreadable data moved to {}",
+                    err,
+                    moved.display()
+                );
+                DirList::new()
+            }
+        };
+        let dirty = prepare_loaded(&config, &mut delegate)?;
+        let load_decay = storage
+            .saved_at()
+            .filter(|_| config.idle_decay_rate > 0.0)
+            .map(|saved_at| {
+                let factor = idle_decay(config.idle_decay_rate, saved_at, dir::now());
+                (saved_at, factor)
+            });
+        if let Some((_, factor)) = load_decay {
+            delegate.decay(factor);
+        }
+        let bookmarks = bookmark::load(&sidecar_path(&config.datafile, "bookmarks"))?;
+        let inodes = if config.track_inode {
+            inode::load(&sidecar_path(&config.datafile, "inodes"))?
+        } else {
+            Inodes::new()
+        };
         Ok(Database {
             config,
             delegate,
-            dirty: false,
+            dirty,
+            storage,
+            bookmarks,
+            inodes,
+            load_decay,
         })
     }
 
-    pub fn save(&self) -> Result<()> {
+    /// Persist entries if anything changed.
+    pub fn save(&mut self) -> Result<()> {
         if !self.dirty {
             return Ok(());
         }
-        let bytes = data::to_bytes(&self.delegate);
-        write_file(Path::new(&self.config.datafile), bytes).context("failed to write datafile")
+        self.storage.save(&self.delegate)?;
+        if self.config.track_inode {
+            let delegate = &self.delegate;
+            self.inodes.retain(|path, _| delegate.contains_key(path));
+            inode::save(&sidecar_path(&self.config.datafile, "inodes"), &self.inodes)?;
+        }
+        let imported: origin::Imported = self
+            .delegate
+            .values()
+            .filter(|dir| dir.origin == Origin::Imported)
+            .map(|dir| dir.path.to_string())
+            .collect();
+        let imported_path = sidecar_path(&self.config.datafile, "imported");
+        if !imported.is_empty() || imported_path.exists() {
+            origin::save(&imported_path, &imported)?;
+        }
+        self.dirty = false;
+        Ok(())
     }
 
-    /// Merge entries from another z-compatible datafile. Existing entries
-    /// keep the higher rank and the most recent access time.
-    pub fn import(&mut self, path: &Path) -> Result<usize> {
-        let incoming = load_datafile(&path.display().to_string())
-            .with_context(|| format!("failed to import from {}", path.display()))?;
-        let count = incoming.len();
-        for (key, dir) in incoming.iter() {
-            match self.delegate.get_mut(key) {
-                Some(existing) => {
-                    existing.rank = existing.rank.max(dir.rank);
-                    existing.last_accessed = existing.last_accessed.max(dir.last_accessed);
-                }
-                None => {
-                    self.delegate.insert(key.clone(), dir.clone());
-                }
+    /// Record a visit to `path` at `at` instead of the current time.
+    ///
+    /// Paths longer than `max_path_len` or containing NUL are skipped with
+    /// a warning: they come from broken shell hooks, not real visits.
+    pub fn insert_at(&mut self, path: Cow<str>, at: Epoch) {
+        let path = match self.config.map_path(&path) {
+            Cow::Owned(mapped) => Cow::Owned(mapped),
+            Cow::Borrowed(_) => path,
+        };
+        if path.len() > self.config.max_path_len || path.contains('\0') {
+            eprintln!(
+                "zcd: skipping invalid path ({} bytes): {:.64}",
+                path.len(),
+                path.escape_debug()
+            );
+            return;
+        }
+        if self.config.require_dir && is_non_dir(Path::new(path.as_ref())) {
+            eprintln!("zcd: skipping non-directory: {}", path);
+            return;
+        }
+        let path = if self.config.fold_case && !self.delegate.contains_key(path.as_ref()) {
+            self.delegate
+                .case_variant(&path)
+                .map_or(path, |stored| Cow::Owned(stored.to_string()))
+        } else {
+            path
+        };
+        if self.is_repeat_insert(&path, at)
+            || self.is_excluded_home(&path)
+            || (self.config.exclude_mode == ExcludeMode::Skip && self.config.is_excluded(&path))
+        {
+            return;
+        }
+        if self.config.track_inode {
+            if let Some(id) = inode::file_id(Path::new(path.as_ref())) {
+                self.inodes.insert(path.to_string(), id);
+            }
+        }
+        self.delegate.insert_at(path, at);
+        self.delegate.age(self.config.max_age as f64);
+        self.dirty = true;
+    }
+
+    /// Insert a path exactly as the shell passed it. The datafile only holds
+    /// UTF-8, so other paths are skipped with a warning: a lossy copy would
+    /// never match a real directory again.
+    pub fn insert_os(&mut self, path: &OsStr) {
+        match path.to_str() {
+            Some(path) => self.insert_or_update(path.to_string().into()),
+            None => warn_non_utf8(&path.to_string_lossy()),
+        }
+    }
+
+    /// Whether `path` is exactly the home directory and `exclude_home` is
+    /// set. Shells start there, so it would otherwise float to the top.
+    fn is_excluded_home(&self, path: &str) -> bool {
+        self.config.exclude_home && home_dir().is_some_and(|home| home == Path::new(path))
+    }
+
+    /// Whether `path` is the most recently visited entry and was visited
+    /// within `insert_debounce_ms` of `at`, i.e. a duplicate hook firing.
+    fn is_repeat_insert(&self, path: &str, at: Epoch) -> bool {
+        let window = self.config.insert_debounce_ms;
+        if window == 0 {
+            return false;
+        }
+        self.delegate
+            .values()
+            .max_by_key(|dir| dir.last_accessed)
+            .is_some_and(|last| {
+                last.path == path && at.saturating_sub(last.last_accessed) * 1000 < window
+            })
+    }
+
+    /// Query knobs derived from the config; callers may adjust them per
+    /// command before passing them back.
+    pub fn query_options(&self) -> QueryOptions {
+        QueryOptions {
+            matcher: Matcher::new(ScoreConfig {
+                match_slash: self.config.slash_bonus,
+                gap_leading: self.config.gap_leading,
+                max_leading_gap: self.config.max_leading_gap,
+                ..ScoreConfig::default()
+            }),
+            stable_order: self.config.stable_order,
+            basename_boost: self.config.basename_boost,
+            depth_penalty: self.config.depth_penalty,
+            exact_path: self.config.exact_path,
+            hidden: match self.config.exclude_mode {
+                ExcludeMode::Hide => self.config.exclude_dirs.clone(),
+                ExcludeMode::Skip => vec![],
+            },
+            weights: self
+                .config
+                .weights
+                .iter()
+                .map(|(prefix, weight)| (prefix.to_string_lossy().into_owned(), *weight))
+                .collect(),
+            ..QueryOptions::default()
+        }
+    }
+
+    /// `path` as it should be shown, after the configured `path_map`.
+    /// Inserts are already mapped; this covers entries stored before the
+    /// map was set up.
+    pub fn map_path<'p>(&self, path: &'p str) -> Cow<'p, str> {
+        self.config.map_path(path)
+    }
+
+    /// Ranked matches for `pattern` with their fuzzy score and positions.
+    pub fn query_scored<S: AsRef<str>>(
+        &self,
+        pattern: S,
+        opts: &QueryOptions,
+    ) -> Vec<ScoredDir<'_>> {
+        self.query_terms(&[pattern.as_ref()], opts)
+    }
+
+    /// Ranked matches for paths that match every term. A single `@name`
+    /// term resolves to the bookmark `name` alone, ahead of any fuzzy
+    /// match; an unknown name falls back to fuzzy matching.
+    pub fn query_terms(&self, terms: &[&str], opts: &QueryOptions) -> Vec<ScoredDir<'_>> {
+        if let Some(target) = match terms {
+            [term] => term.strip_prefix(BOOKMARK_PREFIX),
+            _ => None,
+        }
+        .and_then(|name| self.bookmarks.get(name))
+        {
+            let stored = self.delegate.get(target);
+            let visits = stored.map_or(0.0, |d| d.rank);
+            return vec![ScoredDir {
+                dir: Dir::new(target.as_str()).with_rank(stored.map_or(1.0, |d| d.rank)),
+                score: SCORE_MAX,
+                positions: vec![],
+                visits,
+            }];
+        }
+        self.delegate.query_terms(terms, opts)
+    }
+
+    /// Where a jump to `pattern` should land: the best existing match under
+    /// the configured options. See [`Database::resolve_terms`].
+    pub fn resolve(&self, pattern: &str) -> Result<Option<String>> {
+        Ok(self
+            .resolve_terms(&[pattern], &self.query_options())?
+            .map(|scored| scored.dir.path.into_owned()))
+    }
+
+    /// The single match a jump to `terms` should land on: the best one
+    /// under `opts`, ties broken by frecency and then by `tie_policy`.
+    /// Both `query` and `which` go through this.
+    pub fn resolve_terms(
+        &self,
+        terms: &[&str],
+        opts: &QueryOptions,
+    ) -> Result<Option<ScoredDir<'_>>> {
+        let mut matches = self.query_terms(terms, opts);
+        let Some(first) = matches.first() else {
+            return Ok(None);
+        };
+        let tied = matches
+            .iter()
+            .take_while(|scored| {
+                dir::score_bucket(scored.score) == dir::score_bucket(first.score)
+                    && scored.dir.rank == first.dir.rank
+            })
+            .count();
+        let candidates = matches[..tied].iter().enumerate();
+        let chosen = match self.config.tie_policy {
+            TiePolicy::First => 0,
+            TiePolicy::Recent => {
+                candidates
+                    .max_by_key(|(_, scored)| scored.dir.last_accessed)
+                    .unwrap()
+                    .0
+            }
+            TiePolicy::Shortest => {
+                candidates
+                    .min_by_key(|(_, scored)| scored.dir.path.len())
+                    .unwrap()
+                    .0
             }
+            TiePolicy::Error if tied > 1 => bail!(
+                "{} entries tie for {}: {}; add a keyword to disambiguate",
+                tied,
+                terms.join(" "),
+                matches[..tied]
+                    .iter()
+                    .map(|scored| scored.dir.path.as_ref())
+                    .sorted()
+                    .join(", ")
+            ),
+            TiePolicy::Error => 0,
+        };
+        Ok(Some(matches.swap_remove(chosen)))
+    }
+
+    /// With `track_inode`, find the best stored match for `terms` whose
+    /// directory is gone but reappears, with the same device and inode,
+    /// under a new name in its old parent. Its history moves to the new
+    /// path, which is returned.
+    pub fn follow_rename(&mut self, terms: &[&str], opts: &QueryOptions) -> Option<String> {
+        if !self.config.track_inode {
+            return None;
+        }
+        let opts = QueryOptions {
+            skip_existence_check: true,
+            ..opts.clone()
+        };
+        let (old, new) = self
+            .query_terms(terms, &opts)
+            .into_iter()
+            .map(|scored| scored.dir.path.into_owned())
+            .filter(|path| !Path::new(path).exists())
+            .find_map(|old| {
+                let id = *self.inodes.get(&old)?;
+                let new = inode::find_renamed(Path::new(&old), id)?;
+                Some((old, new))
+            })?;
+        self.delegate.rename(&old, &new);
+        if let Some(id) = self.inodes.remove(&old) {
+            self.inodes.insert(new.clone(), id);
         }
         self.dirty = true;
+        Some(new)
+    }
+
+    /// Number of entries [`Database::query_terms`] would return.
+    pub fn count(&self, terms: &[&str], opts: &QueryOptions) -> usize {
+        self.query_terms(terms, opts).len()
+    }
+
+    /// Point the bookmark `name` at `target`, replacing any previous one.
+    /// Bookmarks are written immediately, independent of [`Database::save`].
+    pub fn bookmark(&mut self, name: &str, target: &Path) -> Result<()> {
+        bookmark::validate_name(name)?;
+        if !target.is_dir() {
+            bail!("bookmark target is not a directory: {}", target.display());
+        }
+        self.bookmarks
+            .insert(name.to_string(), target.to_string_lossy().into_owned());
+        bookmark::save(
+            &sidecar_path(&self.config.datafile, "bookmarks"),
+            &self.bookmarks,
+        )
+    }
+
+    /// Pin or unpin the tracked entry `path`. Pins are written immediately,
+    /// independent of [`Database::save`].
+    pub fn pin(&mut self, path: &str, pinned: bool) -> Result<()> {
+        let Some(dir) = self.delegate.get_mut(path) else {
+            bail!("not tracked: {}", path);
+        };
+        dir.pinned = pinned;
+        let pins: pin::Pins = self
+            .delegate
+            .values()
+            .filter(|dir| dir.pinned)
+            .map(|dir| dir.path.to_string())
+            .collect();
+        pin::save(&sidecar_path(&self.config.datafile, "pins"), &pins)
+    }
+
+    /// The stored entry for `path`, with its raw rank.
+    pub fn get(&self, path: &str) -> Option<&Dir<'_>> {
+        self.delegate.get(path)
+    }
+
+    pub fn list_with(&self, opts: &QueryOptions) -> Vec<Dir<'_>> {
+        self.delegate.list_with(opts)
+    }
+
+    pub fn stats(&self) -> DbStats {
+        self.delegate.stats()
+    }
+
+    /// An owned copy of the current entries that later inserts and deletes
+    /// do not affect. It clones the whole list, so it pays off only when
+    /// many reads share one copy; a single query is cheaper run directly.
+    pub fn snapshot(&self) -> DirList<'static> {
+        self.delegate.snapshot()
+    }
+
+    /// The `limit` most recently visited existing entries, newest first.
+    pub fn recent(&self, limit: usize, opts: &QueryOptions) -> Vec<Dir<'_>> {
+        self.delegate.recent(limit, opts)
+    }
+
+    /// A random existing entry, likelier the higher its frecency.
+    pub fn random(&self, seed: u64, opts: &QueryOptions) -> Option<Dir<'_>> {
+        self.delegate.random(seed, opts)
+    }
+
+    pub fn group_by(&self, depth: usize, opts: &QueryOptions) -> Vec<DirGroup> {
+        self.delegate.group_by(depth, opts)
+    }
+
+    /// Rank every candidate for `terms` but return only the `offset..`
+    /// window of at most `limit` entries, together with the total count.
+    /// Entries filtered out by `opts`, such as missing directories, are
+    /// dropped before ranking, so neither the total nor the window
+    /// counts them.
+    pub fn query_page(
+        &self,
+        terms: &[&str],
+        offset: usize,
+        limit: Option<usize>,
+        opts: &QueryOptions,
+    ) -> QueryPage<'_> {
+        let all = self.query_terms(terms, opts);
+        let total = all.len();
+        let dirs = all
+            .into_iter()
+            .skip(offset)
+            .take(limit.unwrap_or(usize::MAX))
+            .collect();
+        QueryPage { dirs, total }
+    }
+
+    /// Compare a fresh read of the storage with what saving the in-memory
+    /// entries would write. `added` lists entries not yet flushed to disk,
+    /// `removed` entries on disk that are gone from memory; entries that do
+    /// not survive being written in `datafile_format` and read back show up
+    /// as either.
+    ///
+    /// The re-read goes through the same load-time steps as the live
+    /// entries (pins, imported marks, `fold_case` and, while the storage is
+    /// still the one loaded, the same idle decay), so only real divergence
+    /// shows up.
+    pub fn verify(&self) -> Result<DirListDiff> {
+        let mut on_disk = self.storage.load().context("failed to reload datafile")?;
+        prepare_loaded(&self.config, &mut on_disk)?;
+        if let Some((saved_at, factor)) = self.load_decay {
+            if self.storage.saved_at() == Some(saved_at) {
+                on_disk.decay(factor);
+            }
+        }
+        let format = self.config.datafile_format;
+        let mut written = format
+            .from_bytes(format.to_bytes(&self.delegate).as_slice())
+            .context("the in-memory entries would not read back after a save")?;
+        prepare_loaded(&self.config, &mut written)?;
+        Ok(on_disk.diff(&written))
+    }
+
+    /// Insert one path per line from `reader`, skipping blank lines and
+    /// `#` comments. Returns the number of paths inserted.
+    pub fn insert_from_reader<R: BufRead>(&mut self, reader: R) -> Result<usize> {
+        let mut count = 0;
+        for line in reader.split(b'\n') {
+            let line = line.context("failed to read path list")?;
+            let path = match std::str::from_utf8(&line) {
+                Ok(path) => path.trim(),
+                Err(_) => {
+                    warn_non_utf8(&String::from_utf8_lossy(&line));
+                    continue;
+                }
+            };
+            if path.is_empty() || path.starts_with('#') {
+                continue;
+            }
+            self.insert_or_update(path.to_string().into());
+            count += 1;
+        }
         Ok(count)
     }
 
-    /// Write all entries to `path` in the z-compatible pipe format.
-    pub fn export(&self, path: &Path) -> Result<usize> {
-        let bytes = data::to_bytes(&self.delegate);
+    /// Merge entries from a file in `format`. Existing entries keep the
+    /// higher rank and the most recent access time.
+    pub fn import(&mut self, path: &Path, format: DataFormat) -> Result<usize> {
+        let incoming = open_file(path)
+            .and_then(|file| format.from_bytes(file))
+            .with_context(|| format!("failed to import from {}", path.display()))?;
+        Ok(self.merge_imported(incoming))
+    }
+
+    /// [`Database::import`] of a z-format file whose fields are separated
+    /// by `delimiter`.
+    pub fn import_delimited(&mut self, path: &Path, delimiter: Delimiter) -> Result<usize> {
+        let incoming = open_file(path)
+            .and_then(|file| data::from_bytes_delimited(file, delimiter))
+            .with_context(|| format!("failed to import from {}", path.display()))?;
+        Ok(self.merge_imported(incoming))
+    }
+
+    /// [`Database::import`] of a TSV file laid out as `columns`.
+    pub fn import_tsv(&mut self, path: &Path, columns: &TsvColumns) -> Result<usize> {
+        let incoming = open_file(path)
+            .and_then(|file| data::from_tsv_bytes(file, columns))
+            .with_context(|| format!("failed to import from {}", path.display()))?;
+        Ok(self.merge_imported(incoming))
+    }
+
+    /// Merge `incoming`, marking paths that were not tracked yet as
+    /// [`Origin::Imported`].
+    fn merge_imported(&mut self, mut incoming: DirList) -> usize {
+        for dir in incoming.values_mut() {
+            if !self.delegate.contains_key(dir.path.as_ref()) {
+                dir.origin = Origin::Imported;
+            }
+        }
+        self.delegate.merge(&incoming);
+        self.dirty = true;
+        incoming.len()
+    }
+
+    /// Write all entries to `path` in `format`.
+    pub fn export(&self, path: &Path, format: DataFormat) -> Result<usize> {
+        let bytes = format.to_bytes(&self.delegate);
         write_file(path, bytes)
             .with_context(|| format!("failed to export to {}", path.display()))?;
         Ok(self.delegate.len())
@@ -101,20 +683,67 @@ impl Database<'_> {
     pub fn clear(&mut self) -> Result<()> {
         self.delegate.clear_data();
         self.dirty = true;
+        self.storage.clear()
+    }
 
-        let datafile = Path::new(&self.config.datafile);
-        if datafile.exists() {
-            std::fs::remove_file(datafile)
-                .with_context(|| format!("failed to remove datafile: {}", datafile.display()))?;
+    /// [`Database::clear`] plus removal of every zcd-owned sidecar file.
+    pub fn clear_all(&mut self) -> Result<()> {
+        self.clear()?;
+        let sidecars = SIDECAR_EXTENSIONS
+            .iter()
+            .map(|ext| sidecar_path(&self.config.datafile, ext))
+            .chain(quarantined_files(&self.config.datafile));
+        for path in sidecars {
+            if path.exists() {
+                std::fs::remove_file(&path)
+                    .with_context(|| format!("failed to remove {}", path.display()))?;
+            }
         }
         Ok(())
     }
+
+    /// Remove every unpinned entry whose directory no longer exists and
+    /// return their paths. With `dry_run` nothing is removed.
+    pub fn purge_missing(&mut self, dry_run: bool) -> Vec<String> {
+        let mut missing = self.delegate.missing();
+        missing.retain(|path| !self.delegate[path].pinned);
+        if !dry_run {
+            for path in &missing {
+                self.delete(path);
+            }
+        }
+        missing
+    }
+
+    /// Remove every entry whose path matches the glob `pattern`, returning
+    /// the matched paths, sorted. With `dry_run` nothing is removed.
+    pub fn delete_matching(&mut self, pattern: &str, dry_run: bool) -> Vec<String> {
+        let matched = self.delegate.matching_glob(pattern);
+        if !dry_run {
+            for path in &matched {
+                self.delete(path);
+            }
+        }
+        matched
+    }
+
+    /// Rewrite the storage in canonical form (one line per path, best rank
+    /// first). Returns the entry count.
+    ///
+    /// Saves stage in uniquely named temporary files that may belong to a
+    /// concurrent zcd, so compaction leaves them alone.
+    pub fn compact(&mut self) -> Result<usize> {
+        self.storage.save(&self.delegate)?;
+        self.dirty = false;
+        Ok(self.delegate.len())
+    }
 }
 
 #[cfg(test)]
 mod test_db {
     use super::*;
-    use std::fs;
+    use std::fs::{self, File};
+    use std::time::{Duration, SystemTime};
     use tempfile::tempdir;
 
     fn write_config(dir: &Path, datafile: &Path) -> std::path::PathBuf {
@@ -127,6 +756,275 @@ mod test_db {
         config_path
     }
 
+    /// Keeps the serialized entries in memory, shared between clones so a
+    /// "reopened" database sees what the previous one saved.
+    #[derive(Clone, Default)]
+    struct MemoryStorage(std::rc::Rc<std::cell::RefCell<Option<Vec<u8>>>>);
+
+    impl Storage for MemoryStorage {
+        fn load(&self) -> Result<DirList<'static>> {
+            match self.0.borrow().as_deref() {
+                Some(bytes) => data::from_bytes(bytes),
+                None => Ok(DirList::new()),
+            }
+        }
+
+        fn save(&mut self, dirs: &DirList) -> Result<()> {
+            *self.0.borrow_mut() = Some(data::to_bytes(dirs));
+            Ok(())
+        }
+
+        fn clear(&mut self) -> Result<()> {
+            *self.0.borrow_mut() = None;
+            Ok(())
+        }
+    }
+
+    /// Insert, persist, reopen and clear through whatever `open` returns.
+    fn storage_scenario(root: &Path, open: impl Fn() -> Database<'static>) {
+        let target = root.join("proj");
+        fs::create_dir_all(&target).unwrap();
+        let target = target.to_string_lossy().into_owned();
+
+        let mut db = open();
+        db.insert_or_update(target.clone().into());
+        db.insert_or_update(target.clone().into());
+        db.save().unwrap();
+
+        let mut db = open();
+        let hits = db.query("proj");
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].path, target);
+        assert!(db.verify().unwrap().is_empty());
+
+        db.clear().unwrap();
+        db.save().unwrap();
+        assert!(open().list().is_empty());
+    }
+
+    #[test]
+    fn text_and_memory_storage_behave_alike() {
+        let temp_dir = tempdir().unwrap();
+        let datafile_path = temp_dir.path().join("zcddata");
+        let config_path = write_config(temp_dir.path(), &datafile_path);
+        storage_scenario(&temp_dir.path().join("text"), || {
+            Database::new(&config_path).unwrap()
+        });
+        fs::remove_file(&datafile_path).unwrap();
+
+        let memory = MemoryStorage::default();
+        storage_scenario(&temp_dir.path().join("memory"), || {
+            let config = load_config_from_path(&config_path).unwrap();
+            Database::with_storage(config, Box::new(memory.clone())).unwrap()
+        });
+        assert!(
+            !datafile_path.exists(),
+            "memory storage must not touch the datafile"
+        );
+    }
+
+    #[test]
+    fn corrupt_datafile_is_quarantined_and_database_starts_empty() {
+        let temp_dir = tempdir().unwrap();
+        let datafile_path = temp_dir.path().join("zcddata");
+        let config_path = write_config(temp_dir.path(), &datafile_path);
+        fs::write(&datafile_path, "/ok|1.0|100\nnot a valid line\n").unwrap();
+
+        let mut db = Database::new(&config_path).unwrap();
+        assert!(db.list().is_empty());
+        let quarantined = quarantined_files(&datafile_path);
+        assert_eq!(quarantined.len(), 1);
+        assert_eq!(
+            fs::read_to_string(&quarantined[0]).unwrap(),
+            "/ok|1.0|100\nnot a valid line\n",
+            "the bad file is kept verbatim for recovery"
+        );
+
+        db.insert_or_update("/fresh".into());
+        db.save().unwrap();
+        assert!(fs::read_to_string(&datafile_path)
+            .unwrap()
+            .starts_with("/fresh|"));
+    }
+
+    #[test]
+    fn repeated_quarantine_keeps_every_corrupt_file() {
+        let temp_dir = tempdir().unwrap();
+        let datafile_path = temp_dir.path().join("zcddata");
+        let config_path = write_config(temp_dir.path(), &datafile_path);
+        for bad in ["first bad line\n", "second bad line\n"] {
+            fs::write(&datafile_path, bad).unwrap();
+            Database::new(&config_path).unwrap();
+        }
+
+        let mut kept: Vec<_> = quarantined_files(&datafile_path)
+            .iter()
+            .map(|path| fs::read_to_string(path).unwrap())
+            .collect();
+        kept.sort();
+        assert_eq!(kept, ["first bad line\n", "second bad line\n"]);
+
+        Database::new(&config_path).unwrap().clear_all().unwrap();
+        assert!(quarantined_files(&datafile_path).is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unreadable_datafile_is_an_error_not_quarantined() {
+        let temp_dir = tempdir().unwrap();
+        let datafile_path = temp_dir.path().join("zcddata");
+        let config_path = write_config(temp_dir.path(), &datafile_path);
+        // A directory in place of the datafile fails to read with an I/O
+        // error, like a permission problem would, even when run as root.
+        fs::create_dir(&datafile_path).unwrap();
+
+        assert!(Database::new(&config_path).is_err());
+        assert!(datafile_path.is_dir(), "the datafile stays where it was");
+        assert!(quarantined_files(&datafile_path).is_empty());
+    }
+
+    #[test]
+    fn bookmark_resolves_ahead_of_fuzzy_matches() {
+        let temp_dir = tempdir().unwrap();
+        let datafile_path = temp_dir.path().join("zcddata");
+        let config_path = write_config(temp_dir.path(), &datafile_path);
+        let fuzzy_hit = temp_dir.path().join("work");
+        let bookmarked = temp_dir.path().join("elsewhere");
+        fs::create_dir(&fuzzy_hit).unwrap();
+        fs::create_dir(&bookmarked).unwrap();
+
+        let mut db = Database::new(&config_path).unwrap();
+        db.insert_or_update(fuzzy_hit.to_string_lossy().into_owned().into());
+        db.bookmark("work", &bookmarked).unwrap();
+
+        let reopened = Database::new(&config_path).unwrap();
+        let hits = reopened.query_scored("@work", &reopened.query_options());
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].dir.path, bookmarked.to_string_lossy());
+        assert_eq!(hits[0].score, SCORE_MAX);
+
+        assert!(db
+            .bookmark("gone", &temp_dir.path().join("missing"))
+            .is_err());
+    }
+
+    #[test]
+    fn junk_paths_are_not_inserted() {
+        let temp_dir = tempdir().unwrap();
+        let datafile_path = temp_dir.path().join("zcddata");
+        let config_path = write_config(temp_dir.path(), &datafile_path);
+        fs::write(
+            &config_path,
+            format!("datafile={}\nmax_path_len=16", datafile_path.display()),
+        )
+        .unwrap();
+
+        let mut db = Database::new(&config_path).unwrap();
+        db.insert_or_update("/a/path/longer/than/sixteen".into());
+        db.insert_or_update("/nul\0byte".into());
+        assert!(db.delegate.is_empty());
+        assert!(!db.dirty, "skipped inserts do not dirty the database");
+
+        db.insert_or_update("/short".into());
+        assert!(db.delegate.contains_key("/short"));
+    }
+
+    #[test]
+    fn insert_at_uses_the_given_timestamp() {
+        let temp_dir = tempdir().unwrap();
+        let datafile_path = temp_dir.path().join("zcddata");
+        let config_path = write_config(temp_dir.path(), &datafile_path);
+
+        let mut db = Database::new(&config_path).unwrap();
+        db.insert_at("/fixed".into(), 1_600_000_000);
+        assert_eq!(db.get("/fixed").unwrap().last_accessed, 1_600_000_000);
+
+        db.insert_at("/fixed".into(), 1_600_000_100);
+        let stored = db.get("/fixed").unwrap();
+        assert_eq!(stored.last_accessed, 1_600_000_100);
+        assert_eq!(stored.rank, 2.0);
+    }
+
+    #[test]
+    fn compact_rewrites_a_canonical_datafile() {
+        let temp_dir = tempdir().unwrap();
+        let datafile_path = temp_dir.path().join("zcddata");
+        let config_path = write_config(temp_dir.path(), &datafile_path);
+        fs::write(
+            &datafile_path,
+            "/low|1|100\n\n/dup|2|100\n/high|9.00|100\n/dup|4|200\n",
+        )
+        .unwrap();
+        let bak = sidecar_path(&datafile_path, "bak");
+        fs::write(&bak, "backup").unwrap();
+
+        let mut db = Database::new(&config_path).unwrap();
+        assert_eq!(db.compact().unwrap(), 3);
+
+        assert_eq!(
+            fs::read_to_string(&datafile_path).unwrap(),
+            "/high|9.0|100\n/dup|4.0|200\n/low|1.0|100\n"
+        );
+        assert!(bak.exists(), "the single backup is kept");
+    }
+
+    #[test]
+    fn repeat_insert_within_debounce_window_counts_once() {
+        let temp_dir = tempdir().unwrap();
+        let datafile_path = temp_dir.path().join("zcddata");
+        let config_path = temp_dir.path().join("config");
+        fs::write(
+            &config_path,
+            format!(
+                "datafile={}\ninsert_debounce_ms=5000",
+                datafile_path.display()
+            ),
+        )
+        .unwrap();
+
+        let mut db = Database::new(&config_path).unwrap();
+        db.insert_at("/proj".into(), 100);
+        db.insert_at("/proj".into(), 102);
+        assert_eq!(db.get("/proj").unwrap().rank, 1.0, "second hook ignored");
+
+        db.insert_at("/other".into(), 103);
+        db.insert_at("/proj".into(), 104);
+        assert_eq!(
+            db.get("/proj").unwrap().rank,
+            2.0,
+            "coming back from elsewhere is a real visit"
+        );
+        db.insert_at("/proj".into(), 110);
+        assert_eq!(db.get("/proj").unwrap().rank, 3.0, "outside the window");
+    }
+
+    #[test]
+    fn count_respects_filters() {
+        let temp_dir = tempdir().unwrap();
+        let datafile_path = temp_dir.path().join("zcddata");
+        let config_path = write_config(temp_dir.path(), &datafile_path);
+        let mut db = Database::new(&config_path).unwrap();
+        for name in ["proj-a", "proj-b", "proj-c"] {
+            let dir = temp_dir.path().join(name);
+            fs::create_dir(&dir).unwrap();
+            db.insert_or_update(dir.to_string_lossy().into_owned().into());
+        }
+        db.insert_or_update("/definitely/not/a/real/proj".into());
+
+        let opts = db.query_options();
+        assert_eq!(
+            db.count(&["proj"], &opts),
+            3,
+            "missing paths are not counted"
+        );
+        let opts = QueryOptions {
+            exclude: vec!["proj-b".to_string()],
+            ..db.query_options()
+        };
+        assert_eq!(db.count(&["proj"], &opts), 2);
+        assert_eq!(db.count(&["zzzzzz"], &db.query_options()), 0);
+    }
+
     #[test]
     fn clear_empties_database_and_removes_datafile() {
         let temp_dir = tempdir().unwrap();
@@ -155,34 +1053,855 @@ mod test_db {
     }
 
     #[test]
-    fn export_then_import_roundtrips() {
+    fn clear_all_removes_sidecars_only() {
         let temp_dir = tempdir().unwrap();
         let datafile_path = temp_dir.path().join("zcddata");
         let config_path = write_config(temp_dir.path(), &datafile_path);
-        let export_path = temp_dir.path().join("exported");
+        fs::write(&datafile_path, "/dummy/path|1.0|1626969287\n").unwrap();
+        let sidecars: Vec<_> = SIDECAR_EXTENSIONS
+            .iter()
+            .map(|ext| sidecar_path(&datafile_path, ext))
+            .collect();
+        for sidecar in &sidecars {
+            fs::write(sidecar, "").unwrap();
+        }
+        let unrelated = temp_dir.path().join("zcddata.notes");
+        fs::write(&unrelated, "keep me").unwrap();
 
         let mut db = Database::new(&config_path).unwrap();
-        db.insert_or_update(temp_dir.path().to_string_lossy().into_owned().into());
-        assert_eq!(db.export(&export_path).unwrap(), 1);
+        db.clear_all().unwrap();
 
-        let mut db2 = Database::new(&config_path).unwrap();
-        db2.clear_data();
-        assert_eq!(db2.import(&export_path).unwrap(), 1);
-        assert_eq!(db2.list().len(), 1);
+        assert!(db.list().is_empty());
+        assert!(!datafile_path.exists());
+        for sidecar in &sidecars {
+            assert!(!sidecar.exists(), "{} should be removed", sidecar.display());
+        }
+        assert!(unrelated.exists(), "files zcd does not own must survive");
     }
 
     #[test]
-    fn import_merges_keeping_higher_rank_and_newer_access() {
+    fn query_page_walks_the_full_result_set() {
         let temp_dir = tempdir().unwrap();
         let datafile_path = temp_dir.path().join("zcddata");
         let config_path = write_config(temp_dir.path(), &datafile_path);
-        fs::write(&datafile_path, "/shared|10.0|200\n/mine|5.0|100\n").unwrap();
+        let mut db = Database::new(&config_path).unwrap();
+        for i in 0..25 {
+            let dir = temp_dir.path().join(format!("proj-{i:02}"));
+            fs::create_dir(&dir).unwrap();
+            db.insert_or_update(dir.to_string_lossy().into_owned().into());
+        }
+
+        let expected: Vec<String> = db.query("proj").iter().map(|d| d.to_string()).collect();
+
+        let mut paged = vec![];
+        let mut offset = 0;
+        loop {
+            let page = db.query_page(&["proj"], offset, Some(10), &db.query_options());
+            assert_eq!(page.total, 25);
+            if page.dirs.is_empty() {
+                break;
+            }
+            assert!(page.dirs.len() <= 10);
+            offset += page.dirs.len();
+            paged.extend(page.dirs.iter().map(|d| d.dir.to_string()));
+        }
+        assert_eq!(paged, expected);
+
+        let unbounded = db.query_page(&["proj"], 0, None, &db.query_options());
+        assert_eq!(unbounded.dirs.len(), 25);
+    }
+
+    #[test]
+    fn query_page_total_excludes_missing_directories() {
+        let temp_dir = tempdir().unwrap();
+        let datafile_path = temp_dir.path().join("zcddata");
+        let config_path = write_config(temp_dir.path(), &datafile_path);
+        let mut db = Database::new(&config_path).unwrap();
+        for i in 0..10 {
+            let dir = temp_dir.path().join(format!("proj-{i:02}"));
+            fs::create_dir(&dir).unwrap();
+            db.insert_or_update(dir.to_string_lossy().into_owned().into());
+        }
+        // Better ranked than every existing entry, so they would lead
+        // the results if they were counted.
+        for i in 0..5 {
+            let gone = temp_dir.path().join(format!("proj-gone-{i}"));
+            for _ in 0..3 {
+                db.insert_or_update(gone.to_string_lossy().into_owned().into());
+            }
+        }
+
+        let opts = db.query_options();
+        let page = db.query_page(&["proj"], 8, Some(5), &opts);
+        assert_eq!(page.total, 10);
+        assert_eq!(page.dirs.len(), 2);
+        assert!(page
+            .dirs
+            .iter()
+            .all(|d| Path::new(d.dir.path.as_ref()).is_dir()));
+        let first = db.query_page(&["proj"], 0, Some(1), &opts);
+        assert!(!first.dirs[0].dir.path.contains("gone"));
+
+        let mut unchecked = opts.clone();
+        unchecked.skip_existence_check = true;
+        assert_eq!(db.query_page(&["proj"], 0, None, &unchecked).total, 15);
+    }
+
+    #[test]
+    fn scored_query_reports_monotonic_scores_and_positions() {
+        let temp_dir = tempdir().unwrap();
+        let datafile_path = temp_dir.path().join("zcddata");
+        let config_path = write_config(temp_dir.path(), &datafile_path);
+        let mut db = Database::new(&config_path).unwrap();
+        for name in ["zcd", "zz/c/d", "dotfiles/z-config-data", "x/zcd-tool"] {
+            let dir = temp_dir.path().join(name);
+            fs::create_dir_all(&dir).unwrap();
+            db.insert_or_update(dir.to_string_lossy().into_owned().into());
+        }
+
+        let results = db.query_scored("zcd", &db.query_options());
+        assert_eq!(results.len(), 4);
+        assert!(results
+            .windows(2)
+            .all(|w| dir::score_bucket(w[0].score) >= dir::score_bucket(w[1].score)));
+        for result in &results {
+            assert_eq!(result.positions.len(), 3);
+            let chars: Vec<char> = result.dir.path.chars().collect();
+            let matched: String = result.positions.iter().map(|&i| chars[i]).collect();
+            assert_eq!(matched.to_lowercase(), "zcd");
+        }
+    }
+
+    #[test]
+    fn idle_decay_cools_ranks_after_a_long_break() {
+        let temp_dir = tempdir().unwrap();
+        let datafile_path = temp_dir.path().join("zcddata");
+        let config_path = temp_dir.path().join("config");
+        fs::write(
+            &config_path,
+            format!("datafile={}\nidle_decay_rate=0.1", datafile_path.display()),
+        )
+        .unwrap();
+        fs::write(&datafile_path, "/fav|10|1600000000\n").unwrap();
+        let ten_days_ago = SystemTime::now() - Duration::from_secs(10 * 86_400);
+        File::options()
+            .write(true)
+            .open(&datafile_path)
+            .unwrap()
+            .set_modified(ten_days_ago)
+            .unwrap();
+
+        let db = Database::new(&config_path).unwrap();
+        let rank = db.get("/fav").unwrap().rank;
+        assert!((rank - 10.0 * 0.9f64.powi(10)).abs() < 0.01, "{rank}");
+        assert_eq!(idle_decay(0.0, 0, dir::now()), 1.0);
+        assert!(
+            db.verify().unwrap().is_empty(),
+            "decay on load is not divergence"
+        );
+    }
+
+    #[test]
+    fn path_map_stores_and_shows_host_paths() {
+        let temp_dir = tempdir().unwrap();
+        let datafile_path = temp_dir.path().join("zcddata");
+        let config_path = temp_dir.path().join("config");
+        let host = temp_dir.path().join("proj");
+        fs::write(
+            &config_path,
+            format!(
+                "datafile={}\npath_map=[/workspace=>{}]",
+                datafile_path.display(),
+                host.display()
+            ),
+        )
+        .unwrap();
+        fs::write(&datafile_path, "/workspace/legacy|1|1600000000\n").unwrap();
+
+        let mut db = Database::new(&config_path).unwrap();
+        db.insert_or_update("/workspace/app".into());
+        let stored = host.join("app");
+        assert!(db.get(stored.to_str().unwrap()).is_some());
+        assert!(db.get("/workspace/app").is_none());
+        assert_eq!(
+            db.map_path("/workspace/legacy"),
+            host.join("legacy").to_str().unwrap()
+        );
+        assert_eq!(
+            db.map_path(stored.to_str().unwrap()),
+            stored.to_str().unwrap()
+        );
+    }
+
+    #[test]
+    fn purge_missing_removes_exactly_the_missing_entries() {
+        let temp_dir = tempdir().unwrap();
+        let datafile_path = temp_dir.path().join("zcddata");
+        let config_path = write_config(temp_dir.path(), &datafile_path);
+        let kept = temp_dir.path().join("kept");
+        fs::create_dir_all(&kept).unwrap();
+        let kept = kept.to_string_lossy().into_owned();
+        let gone = ["gone-a", "gone-b"]
+            .map(|name| temp_dir.path().join(name).to_string_lossy().into_owned());
+
+        let mut db = Database::new(&config_path).unwrap();
+        for path in [&kept, &gone[0], &gone[1]] {
+            db.insert_or_update(path.clone().into());
+        }
+        db.save().unwrap();
+
+        assert_eq!(db.purge_missing(true), gone);
+        assert!(!db.dirty);
+        assert_eq!(db.delegate.len(), 3);
+
+        assert_eq!(db.purge_missing(false), gone);
+        db.save().unwrap();
+        let reopened = Database::new(&config_path).unwrap();
+        assert_eq!(reopened.delegate.keys().collect::<Vec<_>>(), vec![&kept]);
+    }
+
+    #[test]
+    fn delete_matching_removes_the_glob_matches_in_one_save() {
+        let temp_dir = tempdir().unwrap();
+        let datafile_path = temp_dir.path().join("zcddata");
+        let config_path = write_config(temp_dir.path(), &datafile_path);
+        let root = temp_dir.path().to_string_lossy().into_owned();
+        let scratch = ["scratch/a", "scratch/b"].map(|name| format!("{}/{}", root, name));
+        let kept = [format!("{}/scratch", root), format!("{}/work", root)];
+
+        let mut db = Database::new(&config_path).unwrap();
+        for path in scratch.iter().chain(&kept) {
+            db.insert_or_update(path.clone().into());
+        }
+        db.save().unwrap();
+
+        let pattern = format!("{}/scratch/*", root);
+        assert_eq!(db.delete_matching(&pattern, true), scratch);
+        assert!(!db.dirty);
+        assert_eq!(db.delegate.len(), 4);
+
+        assert_eq!(db.delete_matching(&pattern, false), scratch);
+        db.save().unwrap();
+        let reopened = Database::new(&config_path).unwrap();
+        let mut left: Vec<_> = reopened.delegate.keys().collect();
+        left.sort();
+        assert_eq!(left, kept.iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn pinned_missing_entries_survive_purge() {
+        let temp_dir = tempdir().unwrap();
+        let datafile_path = temp_dir.path().join("zcddata");
+        let config_path = write_config(temp_dir.path(), &datafile_path);
+        let [pinned, unpinned] = ["usb-project", "gone"]
+            .map(|name| temp_dir.path().join(name).to_string_lossy().into_owned());
+
+        let mut db = Database::new(&config_path).unwrap();
+        for path in [&pinned, &unpinned] {
+            db.insert_or_update(path.clone().into());
+        }
+        db.save().unwrap();
+        db.pin(&pinned, true).unwrap();
+        assert!(db.pin("/never/visited", true).is_err());
+
+        let mut db = Database::new(&config_path).unwrap();
+        assert!(db.get(&pinned).unwrap().pinned);
+        assert_eq!(db.purge_missing(false), vec![unpinned.clone()]);
+        db.save().unwrap();
+        let mut db = Database::new(&config_path).unwrap();
+        assert!(db.get(&unpinned).is_none());
+        assert!(db.get(&pinned).is_some());
+
+        db.pin(&pinned, false).unwrap();
+        let mut db = Database::new(&config_path).unwrap();
+        assert_eq!(db.purge_missing(false), [pinned]);
+    }
+
+    #[test]
+    fn fold_case_merges_case_variants() {
+        let temp_dir = tempdir().unwrap();
+        let datafile_path = temp_dir.path().join("zcddata");
+        let config_path = temp_dir.path().join("config");
+        fs::write(
+            &datafile_path,
+            "/Users/u/Projects|5.0|100\n/Users/u/projects|2.0|300\n/Users/u/other|1.0|100\n",
+        )
+        .unwrap();
+        fs::write(
+            &config_path,
+            format!("datafile={}\nfold_case=true", datafile_path.display()),
+        )
+        .unwrap();
+
+        let mut db = Database::new(&config_path).unwrap();
+        assert!(db.get("/Users/u/projects").is_none());
+        let merged = db.get("/Users/u/Projects").unwrap();
+        assert_eq!((merged.rank, merged.last_accessed), (7.0, 300));
+
+        db.insert_at("/USERS/U/PROJECTS".into(), 400);
+        assert_eq!(db.delegate.len(), 2);
+        assert_eq!(db.get("/Users/u/Projects").unwrap().rank, 8.0);
+        db.save().unwrap();
+        assert_eq!(
+            fs::read_to_string(&datafile_path).unwrap(),
+            "/Users/u/Projects|8.0|400\n/Users/u/other|1.0|100\n"
+        );
+
+        fs::write(
+            &config_path,
+            format!("datafile={}", datafile_path.display()),
+        )
+        .unwrap();
+        let mut db = Database::new(&config_path).unwrap();
+        db.insert_at("/users/u/projects".into(), 500);
+        assert_eq!(db.delegate.len(), 3, "case-sensitive by default");
+    }
+
+    #[test]
+    fn merge_files_combines_overlapping_and_disjoint_entries() {
+        let temp_dir = tempdir().unwrap();
+        let a = temp_dir.path().join("a.data");
+        let b = temp_dir.path().join("b.data");
+        let c = temp_dir.path().join("c.data");
+        let out = temp_dir.path().join("out.data");
+        fs::write(&a, "/shared|5.0|100\n/only-a|2.0|50\n").unwrap();
+        fs::write(&b, "/shared|3.0|300\n/only-b|1.0|70\n").unwrap();
+        fs::write(&c, "/only-c|4.0|10\n").unwrap();
+
+        let count = merge_files(&[a.clone(), b.clone()], &out, DataFormat::Z).unwrap();
+        assert_eq!(count, 3);
+        let merged = DataFormat::Z.from_bytes(File::open(&out).unwrap()).unwrap();
+        let shared = merged.get("/shared").unwrap();
+        assert_eq!((shared.rank, shared.last_accessed), (5.0, 300));
+        assert_eq!(merged.get("/only-a").unwrap().rank, 2.0);
+        assert_eq!(merged.get("/only-b").unwrap().last_accessed, 70);
+
+        assert_eq!(merge_files(&[a, c], &out, DataFormat::Z).unwrap(), 3);
+        assert!(merge_files(&[b, temp_dir.path().join("missing")], &out, DataFormat::Z).is_err());
+    }
+
+    #[test]
+    fn diff_files_classifies_added_removed_and_changed() {
+        let temp_dir = tempdir().unwrap();
+        let write = |name: &str, text: &str| {
+            let path = temp_dir.path().join(name);
+            fs::write(&path, text).unwrap();
+            path
+        };
+        let old = write("old.data", "/shared|5.0|100\n/same|2.0|50\n/gone|1.0|10\n");
+        let new = write(
+            "new.data",
+            "/shared|7.0|300\n/same|2.0|90\n/fresh|1.0|400\n",
+        );
+        let other = write("other.data", "/elsewhere|3.0|10\n");
+
+        let diff = diff_files(&old, &new, DataFormat::Z).unwrap();
+        assert_eq!(diff.added, ["/fresh"]);
+        assert_eq!(diff.removed, ["/gone"]);
+        assert_eq!(diff.changed, vec![("/shared".to_string(), 5.0, 7.0)]);
+
+        let disjoint = diff_files(&old, &other, DataFormat::Z).unwrap();
+        assert_eq!(disjoint.added, ["/elsewhere"]);
+        assert_eq!(disjoint.removed, ["/gone", "/same", "/shared"]);
+        assert!(disjoint.changed.is_empty());
+
+        assert!(diff_files(&new, &new, DataFormat::Z).unwrap().is_empty());
+        assert!(diff_files(&old, &temp_dir.path().join("missing"), DataFormat::Z).is_err());
+    }
+
+    #[test]
+    fn exclude_home_skips_only_the_home_directory() {
+        let temp_dir = tempdir().unwrap();
+        let datafile_path = temp_dir.path().join("zcddata");
+        let config_path = temp_dir.path().join("config");
+        fs::write(
+            &config_path,
+            format!("datafile={}\nexclude_home=true", datafile_path.display()),
+        )
+        .unwrap();
+        let home = home_dir().unwrap();
+        let sub = home.join("sub").to_string_lossy().into_owned();
+
+        let mut db = Database::new(&config_path).unwrap();
+        db.insert_or_update(home.to_string_lossy().into_owned().into());
+        db.insert_or_update(format!("{}/", home.display()).into());
+        db.insert_or_update(sub.clone().into());
+        assert_eq!(db.delegate.keys().collect::<Vec<_>>(), vec![&sub]);
+    }
+
+    #[test]
+    fn snapshot_is_unaffected_by_later_changes() {
+        let temp_dir = tempdir().unwrap();
+        let datafile_path = temp_dir.path().join("zcddata");
+        let config_path = write_config(temp_dir.path(), &datafile_path);
+        let mut db = Database::new(&config_path).unwrap();
+        db.insert_at("/kept".into(), 100);
+        db.insert_at("/dropped".into(), 100);
+
+        let snapshot = db.snapshot();
+        db.insert_at("/kept".into(), 200);
+        db.insert_at("/new".into(), 200);
+        db.delete("/dropped");
+
+        assert_eq!(snapshot.len(), 2);
+        let kept = snapshot.get("/kept").unwrap();
+        assert_eq!((kept.rank, kept.last_accessed), (1.0, 100));
+        assert!(snapshot.get("/dropped").is_some());
+        assert!(snapshot.get("/new").is_none());
+        assert_eq!(db.get("/kept").unwrap().rank, 2.0);
+    }
+
+    #[test]
+    fn exclude_mode_skips_or_hides_excluded_dirs() {
+        let temp_dir = tempdir().unwrap();
+        let datafile_path = temp_dir.path().join("zcddata");
+        let muted = temp_dir.path().join("muted");
+        let inside = muted.join("inner");
+        let outside = temp_dir.path().join("muted-not");
+        for dir in [&inside, &outside] {
+            fs::create_dir_all(dir).unwrap();
+        }
+        let paths = [&muted, &inside, &outside].map(|p| p.to_string_lossy().into_owned());
+
+        for mode in ["skip", "hide"] {
+            let config_path = temp_dir.path().join(format!("config-{mode}"));
+            fs::write(
+                &config_path,
+                format!(
+                    "datafile={}-{mode}\nexclude_dirs=[{}]\nexclude_mode={mode}",
+                    datafile_path.display(),
+                    muted.display()
+                ),
+            )
+            .unwrap();
+            let mut db = Database::new(&config_path).unwrap();
+            for path in &paths {
+                db.insert_or_update(path.clone().into());
+            }
+            let shown: Vec<String> = db
+                .list_with(&db.query_options())
+                .into_iter()
+                .map(|dir| dir.path.into_owned())
+                .collect();
+            assert_eq!(shown, vec![paths[2].clone()], "{mode}");
+            let tracked = if mode == "skip" { 1 } else { 3 };
+            assert_eq!(db.delegate.len(), tracked, "{mode}");
+        }
+    }
+
+    #[test]
+    fn datafile_format_selects_the_main_store_layout() {
+        let temp_dir = tempdir().unwrap();
+        for (format, expected) in [
+            ("z", "/srv/app|2.0|1600000100\n"),
+            ("kv", "path=/srv/app;rank=2.0;last=1600000100\n"),
+        ] {
+            let datafile_path = temp_dir.path().join(format!("zcddata-{format}"));
+            let config_path = temp_dir.path().join(format!("config-{format}"));
+            fs::write(
+                &config_path,
+                format!(
+                    "datafile={}\ndatafile_format={format}",
+                    datafile_path.display()
+                ),
+            )
+            .unwrap();
+
+            let mut db = Database::new(&config_path).unwrap();
+            db.insert_at("/srv/app".into(), 1_600_000_000);
+            db.insert_at("/srv/app".into(), 1_600_000_100);
+            db.save().unwrap();
+            assert_eq!(fs::read_to_string(&datafile_path).unwrap(), expected);
+
+            let reopened = Database::new(&config_path).unwrap();
+            let dir = reopened.get("/srv/app").unwrap();
+            assert_eq!((dir.rank, dir.last_accessed), (2.0, 1_600_000_100));
+        }
+    }
+
+    #[test]
+    fn changing_datafile_format_converts_the_existing_datafile() {
+        let temp_dir = tempdir().unwrap();
+        let datafile_path = temp_dir.path().join("zcddata");
+        let config_path = temp_dir.path().join("config");
+        fs::write(&datafile_path, "/srv/app|2.0|1600000100\n").unwrap();
+        fs::write(
+            &config_path,
+            format!("datafile={}\ndatafile_format=kv", datafile_path.display()),
+        )
+        .unwrap();
+
+        let db = Database::new(&config_path).unwrap();
+        let dir = db.get("/srv/app").unwrap();
+        assert_eq!((dir.rank, dir.last_accessed), (2.0, 1_600_000_100));
+        assert_eq!(
+            fs::read_to_string(&datafile_path).unwrap(),
+            "path=/srv/app;rank=2.0;last=1600000100\n"
+        );
+        assert!(quarantined_files(&datafile_path).is_empty());
+    }
+
+    #[test]
+    fn require_dir_rejects_existing_non_directories() {
+        let temp_dir = tempdir().unwrap();
+        let datafile_path = temp_dir.path().join("zcddata");
+        let config_path = temp_dir.path().join("config");
+        fs::write(
+            &config_path,
+            format!("datafile={}\nrequire_dir=true", datafile_path.display()),
+        )
+        .unwrap();
+        let file = temp_dir.path().join("notes.txt");
+        fs::write(&file, "").unwrap();
+        let dir = temp_dir.path().join("dir");
+        fs::create_dir(&dir).unwrap();
+        let mut accepted = vec![dir.clone(), temp_dir.path().join("not-yet")];
+        #[cfg(unix)]
+        {
+            let link = temp_dir.path().join("link");
+            std::os::unix::fs::symlink(&dir, &link).unwrap();
+            accepted.push(link);
+        }
+
+        let mut db = Database::new(&config_path).unwrap();
+        db.insert_or_update(file.to_string_lossy().into_owned().into());
+        assert!(db.get(file.to_str().unwrap()).is_none());
+        for path in &accepted {
+            db.insert_or_update(path.to_string_lossy().into_owned().into());
+            assert!(
+                db.get(path.to_str().unwrap()).is_some(),
+                "{}",
+                path.display()
+            );
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn track_inode_follows_a_renamed_directory() {
+        let temp_dir = tempdir().unwrap();
+        let datafile_path = temp_dir.path().join("zcddata");
+        let config_path = temp_dir.path().join("config");
+        fs::write(
+            &config_path,
+            format!("datafile={}\ntrack_inode=true", datafile_path.display()),
+        )
+        .unwrap();
+        let old = temp_dir.path().join("project-old");
+        let new = temp_dir.path().join("renamed");
+        fs::create_dir(&old).unwrap();
+
+        let mut db = Database::new(&config_path).unwrap();
+        for _ in 0..3 {
+            db.insert_or_update(old.to_string_lossy().into_owned().into());
+        }
+        db.save().unwrap();
+        fs::rename(&old, &new).unwrap();
+
+        let mut db = Database::new(&config_path).unwrap();
+        let opts = db.query_options();
+        assert!(db.resolve_terms(&["project-old"], &opts).unwrap().is_none());
+        let followed = db.follow_rename(&["project-old"], &opts);
+        assert_eq!(followed.as_deref(), new.to_str());
+        db.save().unwrap();
+
+        let db = Database::new(&config_path).unwrap();
+        assert!(db.get(old.to_str().unwrap()).is_none());
+        assert_eq!(db.get(new.to_str().unwrap()).unwrap().rank, 3.0);
+        assert!(fs::read_to_string(sidecar_path(&datafile_path, "inodes"))
+            .unwrap()
+            .ends_with(&format!("|{}\n", new.display())));
+    }
+
+    #[test]
+    fn path_sorted_datafile_changes_one_line_per_visit() {
+        let temp_dir = tempdir().unwrap();
+        let datafile_path = temp_dir.path().join("zcddata");
+        let config_path = temp_dir.path().join("config");
+        fs::write(
+            &config_path,
+            format!("datafile={}\ndatafile_sort=path", datafile_path.display()),
+        )
+        .unwrap();
+        let mut db = Database::new(&config_path).unwrap();
+        for (path, visits) in [("/c", 3), ("/a", 1), ("/b", 2)] {
+            for _ in 0..visits {
+                db.insert_at(path.into(), 100);
+            }
+        }
+        db.save().unwrap();
+        let before = fs::read_to_string(&datafile_path).unwrap();
+        assert_eq!(before, "/a|1.0|100\n/b|2.0|100\n/c|3.0|100\n");
+
+        for _ in 0..5 {
+            db.insert_at("/a".into(), 200);
+        }
+        db.save().unwrap();
+        let after = fs::read_to_string(&datafile_path).unwrap();
+        let changed: Vec<_> = before
+            .lines()
+            .zip(after.lines())
+            .filter(|(old, new)| old != new)
+            .collect();
+        assert_eq!(changed, vec![("/a|1.0|100", "/a|6.0|200")]);
+    }
+
+    #[test]
+    fn resolve_picks_the_best_existing_match() {
+        let temp_dir = tempdir().unwrap();
+        let datafile_path = temp_dir.path().join("zcddata");
+        let config_path = write_config(temp_dir.path(), &datafile_path);
+        let mut db = Database::new(&config_path).unwrap();
+        assert_eq!(db.resolve("proj").unwrap(), None);
+
+        let alpha = temp_dir.path().join("proj-a");
+        fs::create_dir_all(&alpha).unwrap();
+        let alpha = alpha.to_string_lossy().into_owned();
+        db.insert_or_update(alpha.clone().into());
+        assert_eq!(db.resolve("proj").unwrap().as_deref(), Some(alpha.as_str()));
+        assert_eq!(db.resolve("nothing-like-it").unwrap(), None);
+
+        let beta = temp_dir.path().join("proj-b");
+        fs::create_dir_all(&beta).unwrap();
+        let beta = beta.to_string_lossy().into_owned();
+        let gone = temp_dir
+            .path()
+            .join("proj-c")
+            .to_string_lossy()
+            .into_owned();
+        for _ in 0..3 {
+            db.insert_or_update(beta.clone().into());
+            db.insert_or_update(gone.clone().into());
+        }
+        db.insert_or_update(gone.into());
+        assert_eq!(db.resolve("proj").unwrap().as_deref(), Some(beta.as_str()));
+    }
+
+    #[test]
+    fn tie_policy_decides_between_equally_ranked_matches() {
+        let temp_dir = tempdir().unwrap();
+        let datafile_path = temp_dir.path().join("zcddata");
+        let config_path = temp_dir.path().join("config");
+        let long = temp_dir.path().join("deeper/nested/proj");
+        let short = temp_dir.path().join("x/proj");
+        for dir in [&long, &short] {
+            fs::create_dir_all(dir).unwrap();
+        }
+        let [long, short] = [long, short].map(|dir| dir.to_string_lossy().into_owned());
+        let now = dir::now();
+        // Same recency bucket and visit count give equal frecency, and with
+        // no leading gap penalty both paths score the same.
+        let resolve_with = |policy: &str| {
+            fs::write(
+                &config_path,
+                format!(
+                    "datafile={}\nstable_order=true\nmax_leading_gap=0\ntie_policy={}",
+                    datafile_path.display(),
+                    policy
+                ),
+            )
+            .unwrap();
+            let mut db = Database::new(&config_path).unwrap();
+            db.insert_at(long.as_str().into(), now);
+            db.insert_at(short.as_str().into(), now - 60);
+            db.resolve("proj")
+        };
+
+        assert_eq!(resolve_with("first").unwrap(), Some(long.clone()));
+        assert_eq!(resolve_with("recent").unwrap(), Some(long.clone()));
+        assert_eq!(resolve_with("shortest").unwrap(), Some(short.clone()));
+        let err = resolve_with("error").unwrap_err().to_string();
+        assert!(err.contains(&long) && err.contains(&short), "got: {err}");
+        assert!("sometimes".parse::<TiePolicy>().is_err());
+    }
+
+    #[test]
+    fn insert_from_reader_inserts_every_listed_path() {
+        let temp_dir = tempdir().unwrap();
+        let datafile_path = temp_dir.path().join("zcddata");
+        let config_path = write_config(temp_dir.path(), &datafile_path);
+        let mut db = Database::new(&config_path).unwrap();
+
+        let stdin = "/srv/a\n\n# seeded by find\n/srv/b\n  /srv/c  \n";
+        assert_eq!(db.insert_from_reader(stdin.as_bytes()).unwrap(), 3);
+        db.save().unwrap();
+
+        let text = fs::read_to_string(&datafile_path).unwrap();
+        for path in ["/srv/a", "/srv/b", "/srv/c"] {
+            assert!(
+                text.contains(&format!("{path}|")),
+                "{path} missing:\n{text}"
+            );
+        }
+    }
+
+    #[test]
+    fn non_utf8_paths_are_skipped_without_panicking() {
+        let temp_dir = tempdir().unwrap();
+        let datafile_path = temp_dir.path().join("zcddata");
+        let config_path = write_config(temp_dir.path(), &datafile_path);
+        let mut db = Database::new(&config_path).unwrap();
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStrExt;
+            db.insert_os(OsStr::from_bytes(b"/srv/\xffbad"));
+            assert!(!db.dirty);
+        }
+
+        let list: &[u8] = b"/srv/ok\n/srv/\xfe\xffbad\n/srv/also-ok\n";
+        assert_eq!(db.insert_from_reader(list).unwrap(), 2);
+        db.save().unwrap();
+
+        let text = fs::read_to_string(&datafile_path).unwrap();
+        assert_eq!(text.lines().count(), 2, "{text}");
+        assert!(text.contains("/srv/ok|") && text.contains("/srv/also-ok|"));
+    }
+
+    #[test]
+    fn verify_reports_unflushed_divergence() {
+        let temp_dir = tempdir().unwrap();
+        let datafile_path = temp_dir.path().join("zcddata");
+        let config_path = write_config(temp_dir.path(), &datafile_path);
+        fs::write(&datafile_path, "/kept|2.0|100\n/dropped|1.0|100\n").unwrap();
+
+        let mut db = Database::new(&config_path).unwrap();
+        assert!(db.verify().unwrap().is_empty(), "freshly loaded is in sync");
+
+        db.insert_or_update("/kept".into());
+        db.insert_or_update("/new".into());
+        db.delete("/dropped");
+        let diff = db.verify().unwrap();
+        assert_eq!(diff.added, vec!["/new"]);
+        assert_eq!(diff.removed, vec!["/dropped"]);
+        assert_eq!(diff.changed, vec![("/kept".to_string(), 2.0, 3.0)]);
+
+        db.save().unwrap();
+        assert!(db.verify().unwrap().is_empty(), "in sync after save");
+    }
+
+    #[test]
+    fn verify_reports_entries_a_save_would_not_preserve() {
+        let temp_dir = tempdir().unwrap();
+        let datafile_path = temp_dir.path().join("zcddata");
+        let config_path = write_config(temp_dir.path(), &datafile_path);
+        fs::write(&datafile_path, "/kept|2.0|100\n").unwrap();
+
+        let mut db = Database::new(&config_path).unwrap();
+        assert!(db.verify().unwrap().is_empty());
+
+        // A path with an embedded newline and separators is written as two
+        // separate lines, so it would not come back as the entry it was.
+        let mangled = "/x/a|1.0|5\n/x/b".to_string();
+        db.delegate.insert(
+            mangled.clone(),
+            Dir::new(mangled).with_rank(2.0).with_last_accessed(100),
+        );
+        let diff = db.verify().unwrap();
+        assert_eq!(diff.added, vec!["/x/a", "/x/b"]);
+        assert!(diff.removed.is_empty());
+    }
+
+    #[test]
+    fn no_op_change_does_not_rewrite_datafile() {
+        let temp_dir = tempdir().unwrap();
+        let datafile_path = temp_dir.path().join("zcddata");
+        let config_path = write_config(temp_dir.path(), &datafile_path);
+        fs::write(&datafile_path, "/a|2.0|100\n").unwrap();
+        let past = std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000);
+        fs::File::options()
+            .write(true)
+            .open(&datafile_path)
+            .unwrap()
+            .set_modified(past)
+            .unwrap();
+
+        let mut db = Database::new(&config_path).unwrap();
+        // Re-importing the datafile itself marks the db dirty but changes
+        // nothing.
+        db.import(&datafile_path, DataFormat::Z).unwrap();
+        db.save().unwrap();
+        let mtime = fs::metadata(&datafile_path).unwrap().modified().unwrap();
+        assert_eq!(mtime, past, "identical content must not be rewritten");
+
+        db.insert_or_update("/b".into());
+        db.save().unwrap();
+        assert!(fs::read_to_string(&datafile_path).unwrap().contains("/b|"));
+    }
+
+    #[test]
+    fn resolving_a_match_leaves_entries_untouched() {
+        let temp_dir = tempdir().unwrap();
+        let datafile_path = temp_dir.path().join("zcddata");
+        let config_path = write_config(temp_dir.path(), &datafile_path);
+        let target = temp_dir.path().join("proj");
+        fs::create_dir(&target).unwrap();
+        let line = format!("{}|2.0|100\n", target.display());
+        fs::write(&datafile_path, &line).unwrap();
+
+        let mut db = Database::new(&config_path).unwrap();
+        let best = db.query_scored("proj", &db.query_options());
+        assert_eq!(best[0].dir.path, target.to_str().unwrap());
+        db.save().unwrap();
+
+        let stored = db.delegate.get(target.to_str().unwrap()).unwrap();
+        assert_eq!(stored.last_accessed, 100);
+        assert!((stored.rank - 2.0).abs() < 1e-9);
+        assert_eq!(fs::read_to_string(&datafile_path).unwrap(), line);
+    }
+
+    #[test]
+    fn bumping_the_best_match_records_a_visit() {
+        let temp_dir = tempdir().unwrap();
+        let datafile_path = temp_dir.path().join("zcddata");
+        let config_path = write_config(temp_dir.path(), &datafile_path);
+        let target = temp_dir.path().join("proj");
+        fs::create_dir(&target).unwrap();
+        fs::write(&datafile_path, format!("{}|2.0|100\n", target.display())).unwrap();
+
+        let mut db = Database::new(&config_path).unwrap();
+        let best = db.query_scored("proj", &db.query_options())[0]
+            .dir
+            .path
+            .to_string();
+        db.insert_or_update(best.clone().into());
+        db.save().unwrap();
+
+        let reloaded = Database::new(&config_path).unwrap();
+        let stored = reloaded.delegate.get(&best).unwrap();
+        assert!((stored.rank - 3.0).abs() < 1e-9);
+        assert!(stored.last_accessed > 100);
+    }
+
+    #[test]
+    fn export_then_import_roundtrips() {
+        let temp_dir = tempdir().unwrap();
+        let datafile_path = temp_dir.path().join("zcddata");
+        let config_path = write_config(temp_dir.path(), &datafile_path);
+        let export_path = temp_dir.path().join("exported");
+
+        let mut db = Database::new(&config_path).unwrap();
+        db.insert_or_update(temp_dir.path().to_string_lossy().into_owned().into());
+        assert_eq!(db.export(&export_path, DataFormat::Z).unwrap(), 1);
+
+        let mut db2 = Database::new(&config_path).unwrap();
+        db2.clear_data();
+        assert_eq!(db2.import(&export_path, DataFormat::Z).unwrap(), 1);
+        assert_eq!(db2.list().len(), 1);
+    }
+
+    #[test]
+    fn import_merges_keeping_higher_rank_and_newer_access() {
+        let temp_dir = tempdir().unwrap();
+        let datafile_path = temp_dir.path().join("zcddata");
+        let config_path = write_config(temp_dir.path(), &datafile_path);
+        fs::write(&datafile_path, "/shared|10.0|200\n/mine|5.0|100\n").unwrap();
 
         let other = temp_dir.path().join("other-tool-data");
         fs::write(&other, "/shared|3.0|900\n/theirs|7.0|300\n").unwrap();
 
         let mut db = Database::new(&config_path).unwrap();
-        db.import(&other).unwrap();
+        db.import(&other, DataFormat::Z).unwrap();
         db.save().unwrap();
 
         let text = fs::read_to_string(&datafile_path).unwrap();
@@ -193,4 +1912,37 @@ mod test_db {
         assert!(text.contains("/mine|5.0|100"));
         assert!(text.contains("/theirs|7.0|300"));
     }
+
+    #[test]
+    fn imported_entries_stay_marked_until_visited() {
+        let temp_dir = tempdir().unwrap();
+        let datafile_path = temp_dir.path().join("zcddata");
+        let config_path = write_config(temp_dir.path(), &datafile_path);
+        fs::write(&datafile_path, "/mine|5.0|100\n").unwrap();
+        let other = temp_dir.path().join("other-tool-data");
+        fs::write(&other, "/mine|3.0|900\n/old|7.0|300\n/older|2.0|200\n").unwrap();
+
+        let mut db = Database::new(&config_path).unwrap();
+        db.import(&other, DataFormat::Z).unwrap();
+        db.save().unwrap();
+
+        let mut db = Database::new(&config_path).unwrap();
+        let origin = |db: &Database, path| db.get(path).unwrap().origin;
+        assert_eq!(origin(&db, "/mine"), Origin::Visited);
+        assert_eq!(origin(&db, "/old"), Origin::Imported);
+        db.insert_at("/old".into(), 1_000);
+        assert_eq!(origin(&db, "/old"), Origin::Visited);
+        db.save().unwrap();
+
+        let db = Database::new(&config_path).unwrap();
+        let opts = QueryOptions {
+            origin: Some(Origin::Imported),
+            skip_existence_check: true,
+            ..QueryOptions::default()
+        };
+        let paths: Vec<_> = db.list_with(&opts).into_iter().map(|d| d.path).collect();
+        assert_eq!(paths, ["/older"]);
+        let imported = fs::read_to_string(sidecar_path(&datafile_path, "imported")).unwrap();
+        assert_eq!(imported, "/older\n");
+    }
 }
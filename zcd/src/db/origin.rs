@@ -0,0 +1,35 @@
+//! Entries that came from `import` and have not been visited since, kept
+//! in a `<datafile>.imported` sidecar as one path per line so the
+//! z-compatible datafile itself stays unchanged.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+use super::data::write_file;
+
+pub type Imported = BTreeSet<String>;
+
+pub fn load(path: &Path) -> Result<Imported> {
+    if !path.exists() {
+        return Ok(Imported::new());
+    }
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("failed to read imported paths {}", path.display()))?;
+    Ok(text
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+pub fn save(path: &Path, imported: &Imported) -> Result<()> {
+    let mut buffer = String::new();
+    for entry in imported {
+        buffer.push_str(entry);
+        buffer.push('\n');
+    }
+    write_file(path, buffer)
+}
@@ -0,0 +1,34 @@
+//! Pinned entries, kept in a `<datafile>.pins` sidecar as one path per
+//! line. Pinned entries are never decayed, aged out or purged.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+use super::data::write_file;
+
+pub type Pins = BTreeSet<String>;
+
+pub fn load(path: &Path) -> Result<Pins> {
+    if !path.exists() {
+        return Ok(Pins::new());
+    }
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("failed to read pins {}", path.display()))?;
+    Ok(text
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+pub fn save(path: &Path, pins: &Pins) -> Result<()> {
+    let mut buffer = String::new();
+    for pin in pins {
+        buffer.push_str(pin);
+        buffer.push('\n');
+    }
+    write_file(path, buffer)
+}
@@ -0,0 +1,169 @@
+//! Persistence backends for [`super::Database`].
+//!
+//! `Database` only talks to a [`Storage`]; the z-compatible text datafile is
+//! the default (and currently only) backend. Merging and aging stay on
+//! [`DirList`] so every backend shares the same semantics.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use anyhow::{bail, Context, Result};
+
+use super::data::{write_file, DataFormat, LineOrder};
+use super::dir::{DirList, Epoch};
+
+pub trait Storage {
+    /// Read every stored entry. A backend with nothing stored yet returns
+    /// an empty list rather than an error.
+    fn load(&self) -> Result<DirList<'static>>;
+
+    /// Replace the stored entries with `dirs`.
+    fn save(&mut self, dirs: &DirList) -> Result<()>;
+
+    /// Drop everything this backend has stored.
+    fn clear(&mut self) -> Result<()>;
+
+    /// When the stored entries were last written, if the backend knows.
+    fn saved_at(&self) -> Option<Epoch> {
+        None
+    }
+
+    /// Move data that failed to [`Storage::load`] out of the way so a fresh
+    /// start does not overwrite it. Returns where it went; earlier
+    /// quarantined data is never overwritten.
+    fn quarantine(&mut self) -> Result<PathBuf> {
+        bail!("this storage cannot set aside unreadable data")
+    }
+}
+
+/// A line-based datafile, z-compatible `path|rank|last_accessed` unless
+/// another [`DataFormat`] is chosen.
+#[derive(Debug, Clone)]
+pub struct TextStorage {
+    path: PathBuf,
+    format: DataFormat,
+    order: LineOrder,
+}
+
+impl TextStorage {
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        TextStorage {
+            path: path.into(),
+            format: DataFormat::default(),
+            order: LineOrder::default(),
+        }
+    }
+
+    pub fn with_format(mut self, format: DataFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn with_order(mut self, order: LineOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Rewrite the datafile, found to be in `found` format, in the
+    /// configured one.
+    fn convert(&self, found: DataFormat, dirs: &DirList) -> Result<()> {
+        eprintln!(
+            "zcd: {} is in {} format; converting it to {}",
+            self.path.display(),
+            found,
+            self.format
+        );
+        write_file(&self.path, self.format.to_bytes_ordered(dirs, self.order)).with_context(|| {
+            format!(
+                "failed to convert the datafile; run `zcd export --format {}` with \
+                 datafile_format={} and import the result",
+                found, found
+            )
+        })
+    }
+}
+
+impl Storage for TextStorage {
+    /// A datafile written in another [`DataFormat`], as left behind by
+    /// changing `datafile_format`, is converted to the configured one in
+    /// place rather than reported as unreadable.
+    fn load(&self) -> Result<DirList<'static>> {
+        if !self.path.exists() {
+            return Ok(DirList::new());
+        }
+        #[cfg(feature = "mmap")]
+        if self.format == DataFormat::Z {
+            if let Ok(dirs) = super::data::map_file(&self.path) {
+                return Ok(dirs);
+            }
+        }
+        // Read before parsing so an I/O error never reads as corrupt data.
+        let bytes = fs::read(&self.path)
+            .with_context(|| format!("failed to read datafile {}", self.path.display()))?;
+        let err = match self.format.from_bytes(bytes.as_slice()) {
+            Ok(dirs) => return Ok(dirs),
+            Err(err) => err,
+        };
+        let other_formats = [DataFormat::Z, DataFormat::Kv, DataFormat::Tsv]
+            .into_iter()
+            .filter(|format| *format != self.format);
+        for format in other_formats {
+            if let Ok(dirs) = format.from_bytes(bytes.as_slice()) {
+                self.convert(format, &dirs)?;
+                return Ok(dirs);
+            }
+        }
+        Err(err).with_context(|| format!("failed to parse datafile {}", self.path.display()))
+    }
+
+    /// Skips the write when the serialized bytes already match the file,
+    /// so a mutation that ends up a no-op does not rewrite it.
+    fn save(&mut self, dirs: &DirList) -> Result<()> {
+        let bytes = self.format.to_bytes_ordered(dirs, self.order);
+        let unchanged = fs::read(&self.path).is_ok_and(|disk| disk == bytes);
+        if !unchanged {
+            write_file(&self.path, bytes).context("failed to write datafile")?;
+        }
+        Ok(())
+    }
+
+    fn clear(&mut self) -> Result<()> {
+        if self.path.exists() {
+            fs::remove_file(&self.path)
+                .with_context(|| format!("failed to remove datafile: {}", self.path.display()))?;
+        }
+        Ok(())
+    }
+
+    /// The datafile's modification time. Saves that change nothing leave
+    /// the file alone, so this is the last save that changed an entry.
+    fn saved_at(&self) -> Option<Epoch> {
+        let modified = fs::metadata(&self.path).and_then(|m| m.modified()).ok()?;
+        let since_epoch = modified.duration_since(SystemTime::UNIX_EPOCH).ok()?;
+        Some(since_epoch.as_secs())
+    }
+
+    /// Moves the datafile to `<datafile>.corrupt.<timestamp>`, adding a
+    /// counter when that name is already taken.
+    fn quarantine(&mut self) -> Result<PathBuf> {
+        let stamp = format!("corrupt.{}", super::dir::now());
+        let target = std::iter::once(stamp.clone())
+            .chain((1..).map(|n| format!("{}-{}", stamp, n)))
+            .map(|ext| super::sidecar_path(&self.path, &ext))
+            .find(|target| !target.exists())
+            .expect("an unbounded counter always finds a free name");
+        fs::rename(&self.path, &target).with_context(|| {
+            format!(
+                "failed to move {} to {}",
+                self.path.display(),
+                target.display()
+            )
+        })?;
+        Ok(target)
+    }
+}
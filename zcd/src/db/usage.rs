@@ -0,0 +1,80 @@
+//! Local per-command usage counters, kept in the `usage` file of the
+//! state directory as one `name|count` line per command. Nothing here
+//! leaves the machine; recording is off unless `usage_stats=true`.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+
+use super::data::write_file;
+
+pub type Usage = BTreeMap<String, u64>;
+
+/// The counters file inside `state_dir`.
+pub fn usage_path(state_dir: &Path) -> PathBuf {
+    state_dir.join("usage")
+}
+
+/// Counters recorded in `state_dir`; empty when nothing was recorded.
+pub fn command_usage(state_dir: &Path) -> Result<Usage> {
+    let path = usage_path(state_dir);
+    if !path.exists() {
+        return Ok(Usage::new());
+    }
+    let text = fs::read_to_string(&path)
+        .with_context(|| format!("failed to read usage counters {}", path.display()))?;
+    let mut usage = Usage::new();
+    for (line_num, line) in text.lines().enumerate() {
+        let invalid = || anyhow!("invalid usage counter at line {}: {}", line_num + 1, line);
+        let (name, count) = line.split_once('|').ok_or_else(invalid)?;
+        let count = count.parse::<u64>().map_err(|_| invalid())?;
+        usage.insert(name.to_string(), count);
+    }
+    Ok(usage)
+}
+
+/// Add one run of `name` to the counters in `state_dir`, creating the
+/// directory on first use.
+pub fn record_command_usage(state_dir: &Path, name: &str) -> Result<()> {
+    let mut usage = command_usage(state_dir)?;
+    *usage.entry(name.to_string()).or_default() += 1;
+    let mut buffer = String::new();
+    for (name, count) in &usage {
+        buffer.push_str(&format!("{}|{}\n", name, count));
+    }
+    fs::create_dir_all(state_dir)
+        .with_context(|| format!("failed to create {}", state_dir.display()))?;
+    write_file(usage_path(state_dir), buffer)
+}
+
+/// Remove the counters file in `state_dir`, if any.
+pub fn clear_usage(state_dir: &Path) -> Result<()> {
+    let path = usage_path(state_dir);
+    if path.exists() {
+        fs::remove_file(&path).with_context(|| format!("failed to remove {}", path.display()))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test_usage {
+    use super::*;
+
+    #[test]
+    fn counters_accumulate_per_command() {
+        let dir = tempfile::tempdir().unwrap();
+        let state_dir = dir.path().join("state/zcd");
+        assert!(command_usage(&state_dir).unwrap().is_empty());
+
+        record_command_usage(&state_dir, "query").unwrap();
+        record_command_usage(&state_dir, "insert").unwrap();
+        record_command_usage(&state_dir, "query").unwrap();
+
+        let usage = command_usage(&state_dir).unwrap();
+        assert_eq!(usage.get("query"), Some(&2));
+        assert_eq!(usage.get("insert"), Some(&1));
+        assert!(usage_path(&state_dir).is_file());
+    }
+}
@@ -43,12 +43,12 @@ fn lcs_len(needle: &[char], haystack: &[char]) -> usize {
 
 /// Precompute the positional bonus for every haystack character based on
 /// its immediate predecessor (directory separator at the start).
-fn compute_match_bonus(haystack: &[char]) -> Vec<f64> {
+fn compute_match_bonus(haystack: &[char], config: &ScoreConfig) -> Vec<f64> {
     let mut bonuses = Vec::with_capacity(haystack.len());
     let mut prev = CharType::of('/');
     for &ch in haystack {
         let cur = CharType::of(ch);
-        bonuses.push(cur.bonus(prev));
+        bonuses.push(cur.bonus(prev, config));
         prev = cur;
     }
     bonuses
@@ -66,76 +66,185 @@ fn matches_within_tolerance(needle: &[char], haystack: &[char]) -> bool {
     haystack.len() >= required && lcs_len(needle, haystack) >= required
 }
 
-/// Score `needle` against `haystack`.
-///
-/// Returns [`SCORE_MAX`] for an exact (case-insensitive) match,
-/// [`SCORE_MIN`] when too few needle characters appear in order,
-/// and a finite score otherwise (higher is better).
+/// Score `needle` against `haystack` with the default weights.
+/// See [`Matcher::match_score`].
 pub fn match_score(needle: &str, haystack: &str) -> f64 {
-    if needle.is_empty() {
-        return SCORE_MAX;
-    }
-    let needle: Vec<char> = needle.to_lowercase().chars().collect();
-    let haystack: Vec<char> = haystack.to_lowercase().chars().collect();
-    if !matches_within_tolerance(&needle, &haystack) {
-        return SCORE_MIN;
+    Matcher::default().match_score(needle, haystack)
+}
+
+/// Matched haystack positions with the default weights.
+/// See [`Matcher::match_positions`].
+pub fn match_positions(needle: &str, haystack: &str) -> Option<Vec<usize>> {
+    Matcher::default().match_positions(needle, haystack)
+}
+
+/// The scorer, parameterized by a [`ScoreConfig`].
+#[derive(Debug, Clone, Default)]
+pub struct Matcher {
+    config: ScoreConfig,
+}
+
+impl Matcher {
+    pub fn new(config: ScoreConfig) -> Self {
+        Matcher { config }
     }
-    if needle == haystack {
-        return SCORE_MAX;
+
+    /// Score `needle` against `haystack`.
+    ///
+    /// Returns [`SCORE_MAX`] for an exact (case-insensitive) match,
+    /// [`SCORE_MIN`] when too few needle characters appear in order,
+    /// and a finite score otherwise (higher is better).
+    pub fn match_score(&self, needle: &str, haystack: &str) -> f64 {
+        let needle: Vec<char> = needle.to_lowercase().chars().collect();
+        self.score_lowered(&needle, haystack)
     }
-    compute_score(&needle, &haystack)
-}
 
-/// Dynamic program over (needle prefix, haystack prefix).
-///
-/// `best[i][j]` is the best score using the first `i` needle chars against
-/// the first `j` haystack chars; `matched[i][j]` additionally requires
-/// needle char `i` to match haystack char `j`. Transitions:
-///
-/// - match: `best[i-1][j-1] + bonus` or `matched[i-1][j-1] + consecutive`
-/// - gap:   `best[i][j-1] + gap` (trailing gap once all needle chars used)
-/// - skip:  `best[i-1][j] + SCORE_SKIP_NEEDLE` (the tolerance extension)
-fn compute_score(needle: &[char], haystack: &[char]) -> f64 {
-    let n = needle.len();
-    let m = haystack.len();
-    let match_bonus = compute_match_bonus(haystack);
+    /// [`Matcher::match_score`] of one `needle` against many haystacks,
+    /// lowercasing the needle only once.
+    pub fn score_many(&self, needle: &str, haystacks: &[&str]) -> Vec<f64> {
+        let needle: Vec<char> = needle.to_lowercase().chars().collect();
+        haystacks
+            .iter()
+            .map(|haystack| self.score_lowered(&needle, haystack))
+            .collect()
+    }
 
-    // Rolling rows over the needle dimension.
-    let mut best_prev = vec![0.0f64; m + 1];
-    let mut matched_prev = vec![SCORE_MIN; m + 1];
-    // Row 0: no needle chars consumed; gaps before the first match are
-    // charged at the leading rate, as in fzy.
-    for (j, cell) in best_prev.iter_mut().enumerate().skip(1) {
-        *cell = j as f64 * SCORE_GAP_LEADING;
+    fn score_lowered(&self, needle: &[char], haystack: &str) -> f64 {
+        if needle.is_empty() {
+            return SCORE_MAX;
+        }
+        let haystack: Vec<char> = haystack.to_lowercase().chars().collect();
+        if !matches_within_tolerance(needle, &haystack) {
+            return SCORE_MIN;
+        }
+        if needle == haystack {
+            return SCORE_MAX;
+        }
+        self.compute_score(needle, &haystack)
     }
 
-    let mut best_cur = vec![0.0f64; m + 1];
-    let mut matched_cur = vec![SCORE_MIN; m + 1];
+    /// Haystack character indices matched by the best alignment of
+    /// `needle`, or `None` when the needle does not match. Skipped needle
+    /// characters contribute no position.
+    pub fn match_positions(&self, needle: &str, haystack: &str) -> Option<Vec<usize>> {
+        let needle: Vec<char> = needle.to_lowercase().chars().collect();
+        let haystack: Vec<char> = haystack.to_lowercase().chars().collect();
+        if needle.is_empty() {
+            return Some(vec![]);
+        }
+        if !matches_within_tolerance(&needle, &haystack) {
+            return None;
+        }
+        if needle == haystack {
+            return Some((0..needle.len()).collect());
+        }
+        Some(self.compute_positions(&needle, &haystack))
+    }
 
-    for i in 1..=n {
-        let gap = if i == n {
-            SCORE_GAP_TRAILING
+    fn gap(&self, i: usize, n: usize) -> f64 {
+        if i == n {
+            self.config.gap_trailing
         } else {
-            SCORE_GAP_INNER
-        };
-        best_cur[0] = best_prev[0] + SCORE_SKIP_NEEDLE;
-        matched_cur[0] = SCORE_MIN;
-        for j in 1..=m {
-            matched_cur[j] = if needle[i - 1] == haystack[j - 1] {
-                let start = best_prev[j - 1] + match_bonus[j - 1];
-                let extend = matched_prev[j - 1] + SCORE_MATCH_CONSECUTIVE;
-                start.max(extend)
+            self.config.gap_inner
+        }
+    }
+
+    /// Dynamic program over (needle prefix, haystack prefix).
+    ///
+    /// `best[i][j]` is the best score using the first `i` needle chars
+    /// against the first `j` haystack chars; `matched[i][j]` additionally
+    /// requires needle char `i` to match haystack char `j`. Transitions:
+    ///
+    /// - match: `best[i-1][j-1] + bonus` or `matched[i-1][j-1] + consecutive`
+    /// - gap:   `best[i][j-1] + gap` (trailing gap once all needle chars used)
+    /// - skip:  `best[i-1][j] + skip_needle` (the tolerance extension)
+    fn compute_score(&self, needle: &[char], haystack: &[char]) -> f64 {
+        let config = &self.config;
+        let n = needle.len();
+        let m = haystack.len();
+        let match_bonus = compute_match_bonus(haystack, config);
+
+        // Rolling rows over the needle dimension.
+        let mut best_prev = vec![0.0f64; m + 1];
+        let mut matched_prev = vec![SCORE_MIN; m + 1];
+        // Row 0: no needle chars consumed; gaps before the first match are
+        // charged at the leading rate, as in fzy.
+        for (j, cell) in best_prev.iter_mut().enumerate().skip(1) {
+            *cell = config.leading_gap(j);
+        }
+
+        let mut best_cur = vec![0.0f64; m + 1];
+        let mut matched_cur = vec![SCORE_MIN; m + 1];
+
+        for i in 1..=n {
+            let gap = self.gap(i, n);
+            best_cur[0] = best_prev[0] + config.skip_needle;
+            matched_cur[0] = SCORE_MIN;
+            for j in 1..=m {
+                matched_cur[j] = if needle[i - 1] == haystack[j - 1] {
+                    let start = best_prev[j - 1] + match_bonus[j - 1];
+                    let extend = matched_prev[j - 1] + config.match_consecutive;
+                    start.max(extend)
+                } else {
+                    SCORE_MIN
+                };
+                best_cur[j] = matched_cur[j]
+                    .max(best_cur[j - 1] + gap)
+                    .max(best_prev[j] + config.skip_needle);
+            }
+            std::mem::swap(&mut best_prev, &mut best_cur);
+            std::mem::swap(&mut matched_prev, &mut matched_cur);
+        }
+        best_prev[m]
+    }
+
+    /// Same DP as [`Matcher::compute_score`] but keeping full matrices so
+    /// the optimal alignment can be traced back from `(n, m)`.
+    fn compute_positions(&self, needle: &[char], haystack: &[char]) -> Vec<usize> {
+        let config = &self.config;
+        let n = needle.len();
+        let m = haystack.len();
+        let match_bonus = compute_match_bonus(haystack, config);
+
+        let mut best = vec![vec![0.0f64; m + 1]; n + 1];
+        let mut matched = vec![vec![SCORE_MIN; m + 1]; n + 1];
+        for (j, cell) in best[0].iter_mut().enumerate().skip(1) {
+            *cell = config.leading_gap(j);
+        }
+        for i in 1..=n {
+            let gap = self.gap(i, n);
+            best[i][0] = best[i - 1][0] + config.skip_needle;
+            for j in 1..=m {
+                if needle[i - 1] == haystack[j - 1] {
+                    let start = best[i - 1][j - 1] + match_bonus[j - 1];
+                    let extend = matched[i - 1][j - 1] + config.match_consecutive;
+                    matched[i][j] = start.max(extend);
+                }
+                best[i][j] = matched[i][j]
+                    .max(best[i][j - 1] + gap)
+                    .max(best[i - 1][j] + config.skip_needle);
+            }
+        }
+
+        let mut positions = Vec::with_capacity(n);
+        let (mut i, mut j) = (n, m);
+        let mut match_required = false;
+        while i > 0 && j > 0 {
+            if matched[i][j] > SCORE_MIN && (match_required || matched[i][j] == best[i][j]) {
+                // A consecutive extension forces the previous cell to be a match.
+                match_required = matched[i][j] == matched[i - 1][j - 1] + config.match_consecutive;
+                positions.push(j - 1);
+                i -= 1;
+                j -= 1;
+            } else if best[i][j] == best[i][j - 1] + self.gap(i, n) {
+                j -= 1;
             } else {
-                SCORE_MIN
-            };
-            best_cur[j] = matched_cur[j]
-                .max(best_cur[j - 1] + gap)
-                .max(best_prev[j] + SCORE_SKIP_NEEDLE);
+                i -= 1;
+            }
         }
-        std::mem::swap(&mut best_prev, &mut best_cur);
-        std::mem::swap(&mut matched_prev, &mut matched_cur);
+        positions.reverse();
+        positions
     }
-    best_prev[m]
 }
 
 #[cfg(test)]
@@ -250,6 +359,99 @@ mod test_fzy {
         assert!(score > SCORE_MIN);
     }
 
+    #[test]
+    fn positions_prefer_path_component_starts() {
+        assert_eq!(
+            match_positions("amor", "app/models/order"),
+            Some(vec![0, 4, 11, 12])
+        );
+    }
+
+    #[test]
+    fn positions_follow_consecutive_runs() {
+        assert_eq!(match_positions("zcd", "/home/zcd"), Some(vec![6, 7, 8]));
+    }
+
+    #[test]
+    fn positions_omit_skipped_needle_chars() {
+        let positions = match_positions("labexample", "lab/exmaple").unwrap();
+        assert_eq!(positions.len(), 9, "one transposed char is skipped");
+        assert!(positions.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn positions_none_without_match() {
+        assert_eq!(match_positions("xyz", "abc"), None);
+    }
+
+    #[test]
+    fn higher_slash_bonus_reorders_candidates() {
+        // With default weights the consecutive run in `/a/fb` wins; a
+        // stronger slash bonus favors `/f/b`, where both chars start a
+        // path component.
+        let run = "/a/fb";
+        let components = "/f/b";
+        assert!(match_score("fb", run) > match_score("fb", components));
+
+        let matcher = Matcher::new(ScoreConfig {
+            match_slash: 1.2,
+            ..ScoreConfig::default()
+        });
+        assert!(matcher.match_score("fb", components) > matcher.match_score("fb", run));
+    }
+
+    #[test]
+    fn capped_leading_gap_stops_burying_late_matches() {
+        let early = format!("/{}/proj", "a".repeat(100));
+        let late = format!("/{}/proj", "a".repeat(300));
+        let uncapped = Matcher::default();
+        assert!(uncapped.match_score("proj", &early) > uncapped.match_score("proj", &late));
+
+        let capped = Matcher::new(ScoreConfig {
+            max_leading_gap: 0.2,
+            ..ScoreConfig::default()
+        });
+        assert_eq!(
+            capped.match_score("proj", &early),
+            capped.match_score("proj", &late)
+        );
+        assert!(capped.match_score("proj", &late) > uncapped.match_score("proj", &late));
+        assert_eq!(
+            capped.match_positions("proj", &late),
+            uncapped.match_positions("proj", &late)
+        );
+    }
+
+    #[test]
+    fn dotfile_components_get_dot_and_slash_bonuses() {
+        // `c` right after the dot earns the dot bonus.
+        assert!(match_score("config", "/.config") > match_score("config", "/xconfig"));
+        // The dot itself starts the component, like any first character.
+        assert!(match_score(".c", "/home/.c") > match_score(".c", "/homex.c"));
+    }
+
+    #[test]
+    fn score_many_matches_per_call_scores() {
+        let haystacks = ["/home/zcd", "/ZCD", "/tmp/other", "", "/z/c/d"];
+        let matcher = Matcher::default();
+        for needle in ["zcd", "ZcD", "", "zdc"] {
+            let batch = matcher.score_many(needle, &haystacks);
+            let single: Vec<f64> = haystacks
+                .iter()
+                .map(|h| matcher.match_score(needle, h))
+                .collect();
+            assert_eq!(batch, single, "needle {needle:?}");
+        }
+    }
+
+    #[test]
+    fn default_matcher_uses_score_constants() {
+        assert_eq!(
+            Matcher::new(ScoreConfig::default()).match_score("zcd", "/home/zcd"),
+            match_score("zcd", "/home/zcd")
+        );
+    }
+
     #[test]
     fn lcs_len_basics() {
         let a: Vec<char> = "labexample".chars().collect();
@@ -6,5 +6,5 @@
 mod fzy;
 mod score;
 
-pub use fzy::{has_match, match_score};
-pub use score::{SCORE_MAX, SCORE_MIN};
+pub use fzy::{has_match, match_positions, match_score, Matcher};
+pub use score::{ScoreConfig, SCORE_GAP_LEADING, SCORE_MATCH_SLASH, SCORE_MAX, SCORE_MIN};
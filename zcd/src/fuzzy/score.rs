@@ -16,6 +16,47 @@ pub const SCORE_MATCH_DOT: f64 = 0.6;
 /// skipping is always a last resort.
 pub const SCORE_SKIP_NEEDLE: f64 = -1.0;
 
+/// Tunable scoring weights. `Default` reproduces the constants above.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScoreConfig {
+    pub gap_leading: f64,
+    pub gap_trailing: f64,
+    pub gap_inner: f64,
+    pub match_consecutive: f64,
+    pub match_slash: f64,
+    pub match_word: f64,
+    pub match_capital: f64,
+    pub match_dot: f64,
+    pub skip_needle: f64,
+    /// Most that leading gaps may cost in total, so a strong match late in
+    /// a long path is not buried by its prefix. Infinite means uncapped.
+    pub max_leading_gap: f64,
+}
+
+impl ScoreConfig {
+    /// Penalty for `len` haystack characters before the first match.
+    pub fn leading_gap(&self, len: usize) -> f64 {
+        (len as f64 * self.gap_leading).max(-self.max_leading_gap)
+    }
+}
+
+impl Default for ScoreConfig {
+    fn default() -> Self {
+        ScoreConfig {
+            gap_leading: SCORE_GAP_LEADING,
+            gap_trailing: SCORE_GAP_TRAILING,
+            gap_inner: SCORE_GAP_INNER,
+            match_consecutive: SCORE_MATCH_CONSECUTIVE,
+            match_slash: SCORE_MATCH_SLASH,
+            match_word: SCORE_MATCH_WORD,
+            match_capital: SCORE_MATCH_CAPITAL,
+            match_dot: SCORE_MATCH_DOT,
+            skip_needle: SCORE_SKIP_NEEDLE,
+            max_leading_gap: f64::INFINITY,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub enum CharType {
     Upper,
@@ -42,19 +83,25 @@ impl CharType {
 
     /// Bonus awarded for matching a character of this type when the
     /// previous haystack character is `prev`.
-    pub fn bonus(self, prev: CharType) -> f64 {
+    pub fn bonus(self, prev: CharType, config: &ScoreConfig) -> f64 {
         match self {
             CharType::Upper => match prev {
-                CharType::Lower => SCORE_MATCH_CAPITAL,
-                CharType::Dot => SCORE_MATCH_DOT,
-                CharType::Sep => SCORE_MATCH_WORD,
-                CharType::Slash => SCORE_MATCH_SLASH,
+                CharType::Lower => config.match_capital,
+                CharType::Dot => config.match_dot,
+                CharType::Sep => config.match_word,
+                CharType::Slash => config.match_slash,
                 _ => 0.0,
             },
             CharType::Lower | CharType::Digit => match prev {
-                CharType::Sep => SCORE_MATCH_WORD,
-                CharType::Slash => SCORE_MATCH_SLASH,
-                CharType::Dot => SCORE_MATCH_DOT,
+                CharType::Sep => config.match_word,
+                CharType::Slash => config.match_slash,
+                CharType::Dot => config.match_dot,
+                _ => 0.0,
+            },
+            // The dot of a dotfile component (`/.config`) starts that
+            // component just like a letter would.
+            CharType::Dot => match prev {
+                CharType::Slash => config.match_slash,
                 _ => 0.0,
             },
             _ => 0.0,
@@ -11,7 +11,7 @@ use std::process;
 
 fn main() {
     if !config_exists() {
-        if let Err(e) = generate_config_file() {
+        if let Err(e) = generate_config_file(false) {
             eprintln!("{:?}", e);
             process::exit(1);
         }